@@ -0,0 +1,229 @@
+use super::{emitter::Emitter, Type};
+
+/// Integer argument registers of the System V AMD64 calling convention, in order.
+pub const ARG_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// Vector argument registers of the System V AMD64 calling convention, in order.
+pub const SSE_ARG_REGISTERS: [&str; 8] = [
+    "%xmm0", "%xmm1", "%xmm2", "%xmm3", "%xmm4", "%xmm5", "%xmm6", "%xmm7",
+];
+
+/// Integer registers holding consecutive INTEGER eightbytes of a return value
+pub const RET_REGISTERS: [&str; 2] = ["%rax", "%rdx"];
+
+/// Vector registers holding consecutive SSE eightbytes of a return value
+pub const SSE_RET_REGISTERS: [&str; 2] = ["%xmm0", "%xmm1"];
+
+/// Class of an eightbyte of a value, deciding where it is passed or returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgClass {
+    Integer,
+    Sse,
+    Memory,
+}
+
+/// Classifies a value of type `ty` one eightbyte at a time. Values that do not travel in
+/// registers are classified as a single [`ArgClass::Memory`].
+pub fn classify(ty: &Type) -> Vec<ArgClass> {
+    // Anything larger than two eightbytes is passed in memory
+    if ty.size() > 16 {
+        return vec![ArgClass::Memory];
+    }
+
+    let mut classes = vec![None; (ty.size() as usize).div_ceil(8)];
+    classify_at(ty, 0, &mut classes);
+    classes
+        .into_iter()
+        .map(|class| class.unwrap_or(ArgClass::Sse))
+        .collect()
+}
+
+/// Picks a register for each eightbyte of a value from the integer and vector registers not
+/// yet in use. The value is only assigned registers if all of its eightbytes fit, otherwise
+/// it travels in memory and no registers are used up.
+pub fn assign_registers(
+    classes: &[ArgClass],
+    (int_regs, int_used): (&[&'static str], &mut usize),
+    (sse_regs, sse_used): (&[&'static str], &mut usize),
+) -> Option<Vec<&'static str>> {
+    let ints = classes.iter().filter(|c| **c == ArgClass::Integer).count();
+    let sses = classes.iter().filter(|c| **c == ArgClass::Sse).count();
+    if classes.contains(&ArgClass::Memory)
+        || *int_used + ints > int_regs.len()
+        || *sse_used + sses > sse_regs.len()
+    {
+        return None;
+    }
+
+    Some(
+        classes
+            .iter()
+            .map(|class| {
+                let (regs, used) = match class {
+                    ArgClass::Sse => (sse_regs, &mut *sse_used),
+                    _ => (int_regs, &mut *int_used),
+                };
+                *used += 1;
+                regs[*used - 1]
+            })
+            .collect(),
+    )
+}
+
+/// Loads the eightbyte `offset` bytes into the `size` byte object at `(base)` into `reg`,
+/// never reading past the end of the object. Clobbers `%r10` and `%r11`.
+pub fn emit_load_eightbyte(em: &mut Emitter, base: &str, offset: isize, size: isize, reg: &str) {
+    match (size - offset).min(8) {
+        8 => em.emit_instr(&format!("mov {}({}), %r11", offset, base)),
+        4 => em.emit_instr(&format!("movl {}({}), %r11d", offset, base)),
+        2 => em.emit_instr(&format!("movzwl {}({}), %r11d", offset, base)),
+        1 => em.emit_instr(&format!("movzbl {}({}), %r11d", offset, base)),
+        n => {
+            // Assembled a byte at a time, starting from the most significant one
+            em.emit_instr("xor %r11d, %r11d");
+            for byte in (offset..offset + n).rev() {
+                em.emit_instr("shl $8, %r11");
+                em.emit_instr(&format!("movzbl {}({}), %r10d", byte, base));
+                em.emit_instr("or %r10, %r11");
+            }
+        }
+    }
+
+    if reg.starts_with("%xmm") {
+        em.emit_instr(&format!("movq %r11, {}", reg));
+    } else if reg != "%r11" {
+        em.emit_instr(&format!("mov %r11, {}", reg));
+    }
+}
+
+/// Stores the whole eightbyte in `reg` at `offset(base)`
+pub fn emit_store_eightbyte(em: &mut Emitter, reg: &str, base: &str, offset: isize) {
+    let mov = if reg.starts_with("%xmm") {
+        "movq"
+    } else {
+        "mov"
+    };
+    em.emit_instr(&format!("{} {}, {}({})", mov, reg, offset, base));
+}
+
+/// Merges the classes of each scalar in `ty`, which is located `offset` bytes into the value
+fn classify_at(ty: &Type, offset: isize, classes: &mut [Option<ArgClass>]) {
+    match ty.unqualified() {
+        Type::Array(elem, len) => {
+            for i in 0..*len as isize {
+                classify_at(elem, offset + i * elem.size(), classes);
+            }
+        }
+        Type::Struct(s) => {
+            for member in s.members() {
+                classify_at(&member.ty, offset + member.offset, classes);
+            }
+        }
+        // Left unclassified, so an eightbyte holding only floating point values is SSE
+        Type::Float | Type::Double => {}
+        _ => {
+            // An eightbyte holding any integer is INTEGER, otherwise it is SSE
+            classes[offset as usize / 8] = Some(ArgClass::Integer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ty::{StructRef, TagKind};
+
+    const INT: Type = Type::Int { signed: true };
+    const LONG: Type = Type::Long { signed: true };
+
+    fn struct_of(members: Vec<Type>) -> Type {
+        let s = StructRef::new(TagKind::Struct, None);
+        s.define(
+            members
+                .into_iter()
+                .enumerate()
+                .map(|(i, ty)| (format!("m{}", i), ty))
+                .collect(),
+        );
+        Type::Struct(s)
+    }
+
+    #[test]
+    fn classify_scalars() {
+        assert_eq!(classify(&INT), vec![ArgClass::Integer]);
+        assert_eq!(classify(&INT.pointer_to()), vec![ArgClass::Integer]);
+        assert_eq!(classify(&Type::Double), vec![ArgClass::Sse]);
+    }
+
+    #[test]
+    fn classify_structs() {
+        assert_eq!(classify(&struct_of(vec![INT])), vec![ArgClass::Integer]);
+        assert_eq!(
+            classify(&struct_of(vec![INT, INT.pointer_to()])),
+            vec![ArgClass::Integer, ArgClass::Integer]
+        );
+        assert_eq!(
+            classify(&struct_of(vec![INT.array_of(4)])),
+            vec![ArgClass::Integer, ArgClass::Integer]
+        );
+        assert_eq!(
+            classify(&struct_of(vec![INT, INT, INT])),
+            vec![ArgClass::Integer, ArgClass::Integer]
+        );
+        assert_eq!(
+            classify(&struct_of(vec![LONG, LONG, LONG])),
+            vec![ArgClass::Memory]
+        );
+
+        // Floats sharing an eightbyte with an integer are passed with it
+        assert_eq!(
+            classify(&struct_of(vec![Type::Float, Type::Float, Type::Double])),
+            vec![ArgClass::Sse, ArgClass::Sse]
+        );
+        assert_eq!(
+            classify(&struct_of(vec![Type::Float, INT, Type::Double])),
+            vec![ArgClass::Integer, ArgClass::Sse]
+        );
+    }
+
+    #[test]
+    fn assign() {
+        let (mut ints, mut sses) = (5, 0);
+        let pair = [ArgClass::Integer, ArgClass::Integer];
+
+        // Both eightbytes must fit, otherwise nothing is used up
+        assert_eq!(
+            assign_registers(
+                &pair,
+                (&ARG_REGISTERS, &mut ints),
+                (&SSE_ARG_REGISTERS, &mut sses)
+            ),
+            None
+        );
+        assert_eq!(ints, 5);
+        assert_eq!(
+            assign_registers(
+                &[ArgClass::Integer],
+                (&ARG_REGISTERS, &mut ints),
+                (&SSE_ARG_REGISTERS, &mut sses)
+            ),
+            Some(vec!["%r9"])
+        );
+        assert_eq!(
+            assign_registers(
+                &[ArgClass::Sse, ArgClass::Integer],
+                (&RET_REGISTERS, &mut 0),
+                (&SSE_RET_REGISTERS, &mut sses)
+            ),
+            Some(vec!["%xmm0", "%rax"])
+        );
+        assert_eq!(
+            assign_registers(
+                &[ArgClass::Memory],
+                (&ARG_REGISTERS, &mut 0),
+                (&SSE_ARG_REGISTERS, &mut 0)
+            ),
+            None
+        );
+    }
+}
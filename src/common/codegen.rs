@@ -1,4 +1,10 @@
-use super::{emitter::Emitter, env::Environment, label_tracker::LabelTracker, Span, Spanned};
+use super::{
+    emitter::Emitter,
+    env::Environment,
+    label_tracker::{LabelKind, LabelTracker},
+    ty::TagKind,
+    Span, Spanned, Type,
+};
 use ariadne::{Color, Label};
 use color_eyre::owo_colors::OwoColorize;
 use std::ops::Range;
@@ -14,6 +20,130 @@ pub enum CodegenError<'src> {
 
     #[error("invalid assignment target")]
     InvalidAssignmentTarget,
+
+    #[error("cannot assign to a const-qualified object")]
+    ConstAssignment(Type, Option<Spanned<&'src str>>),
+
+    #[error("cannot increment or decrement value")]
+    InvalidIncrement(Type),
+
+    #[error("indirection requires a pointer operand")]
+    InvalidDereference(Type),
+
+    #[error("cannot take the address of an rvalue")]
+    InvalidAddressOf,
+
+    #[error("invalid initializer")]
+    InvalidInitializer(Type),
+
+    #[error("excess elements in initializer")]
+    ExcessInitializers(Type),
+
+    #[error("invalid designator")]
+    InvalidDesignator(Type),
+
+    #[error("array size missing")]
+    MissingArrayLength(&'src str),
+
+    #[error("called object is not a function")]
+    InvalidCallTarget,
+
+    #[error("redefinition of tag")]
+    RedefinedTag(TagKind, &'src str, Span),
+
+    #[error("tag used with the wrong kind")]
+    MismatchedTag(TagKind, &'src str, Span),
+
+    #[error("duplicate member")]
+    DuplicateMember(&'src str, Span),
+
+    #[error("incomplete type")]
+    IncompleteType(Type),
+
+    #[error("member reference base type is not a struct")]
+    InvalidMemberAccess(Type),
+
+    #[error("no such member")]
+    UnknownMember(&'src str, Type),
+
+    #[error("incompatible types")]
+    IncompatibleTypes(Type, Type),
+
+    #[error("invalid operands to binary expression")]
+    InvalidOperands(Type, Type),
+
+    #[error("invalid operand to unary expression")]
+    InvalidOperand(Type),
+
+    #[error("condition is not a scalar")]
+    NonScalarCondition(Type),
+
+    #[error("invalid cast")]
+    InvalidCast(Type, Type),
+
+    #[error("expected a 'va_list'")]
+    InvalidVaList(Type),
+
+    #[error("'va_start' used in a function with fixed parameters")]
+    VaStartOutsideVariadic,
+
+    #[error("second argument to 'va_start' is not the last named parameter")]
+    NotLastParam(Option<&'src str>),
+
+    #[error("unsupported type for 'va_arg'")]
+    InvalidVaArg(Type),
+
+    #[error("expression is not an integer constant expression")]
+    NotConstant,
+
+    #[error("initializer element is not a compile-time constant")]
+    NotConstantInitializer,
+
+    #[error("array has negative size")]
+    InvalidArrayLength(i64),
+
+    #[error("conflicting types for function")]
+    ConflictingFunction(&'src str, Span),
+
+    #[error("redefinition of function")]
+    RedefinedFunction(&'src str, Span),
+
+    /// The name, where it was first declared and whether that gave it external linkage
+    #[error("conflicting linkage")]
+    ConflictingLinkage(&'src str, Span, bool),
+
+    #[error("wrong number of arguments to function call")]
+    ArgumentCount(Option<&'src str>, usize, Span),
+
+    #[error("incompatible argument type")]
+    InvalidArgument(Type, Type, Span),
+
+    #[error("statement requires expression of integer type")]
+    InvalidSwitch(Type),
+
+    #[error("case label not within a switch statement")]
+    CaseOutsideSwitch,
+
+    #[error("duplicate case value")]
+    DuplicateCase(i64, Span),
+
+    #[error("multiple default labels in one switch")]
+    DuplicateDefault(Span),
+
+    #[error("use of undeclared label '{0}'")]
+    UndefinedLabel(&'src str),
+
+    #[error("redefinition of label '{0}'")]
+    DuplicateLabel(&'src str, Span),
+
+    #[error("label '{0}' defined but not used")]
+    UnusedLabel(&'src str),
+
+    #[error("break statement not within a loop")]
+    BreakOutsideLoop,
+
+    #[error("continue statement not within a loop")]
+    ContinueOutsideLoop,
 }
 
 pub trait IntoLabels {
@@ -49,12 +179,502 @@ impl<'src> IntoLabels for Spanned<CodegenError<'src>> {
                 vec![Label::new((src_id.clone(), span.into_range()))
                     .with_message("unable to assign to this")]
             }
+
+            (Error::ConstAssignment(ty, declaration), span) => {
+                let mut labels = vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message(format!("this has type '{}'", ty.bright_black()))];
+                if let Some((name, decl_span)) = declaration {
+                    labels.push(
+                        Label::new((src_id, decl_span.into_range()))
+                            .with_message(format!("'{}' declared here", name.bright_black())),
+                    );
+                }
+                labels
+            }
+
+            (Error::InvalidIncrement(ty), span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message(format!("this has type '{}'", ty.bright_black()))]
+            }
+
+            (Error::InvalidDereference(ty), span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message(format!("operand has type '{}'", ty.bright_black()))]
+            }
+
+            (Error::InvalidAddressOf, span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message("this has no address")]
+            }
+
+            (Error::InvalidInitializer(ty), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "cannot initialize an object of type '{}' with this",
+                        ty.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::ExcessInitializers(ty), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "this does not fit in an object of type '{}'",
+                        ty.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::InvalidDesignator(ty), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "this does not designate an element of '{}'",
+                        ty.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::MissingArrayLength(name), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "'{}' needs a length, or an initializer list or string to count",
+                        name.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::InvalidCallTarget, span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message("unable to call this")]
+            }
+
+            (Error::RedefinedTag(kind, tag, initial_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range())).with_message(format!(
+                        "{} '{}' initially defined here",
+                        kind,
+                        tag.bright_black()
+                    )),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message(format!("defined '{}' again here", tag.bright_black())),
+                ]
+            }
+
+            (Error::MismatchedTag(kind, tag, initial_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range())).with_message(format!(
+                        "{} '{}' initially declared here",
+                        kind,
+                        tag.bright_black()
+                    )),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message("used as a different kind of tag here"),
+                ]
+            }
+
+            (Error::DuplicateMember(name, initial_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range())).with_message(format!(
+                        "member '{}' initially declared here",
+                        name.bright_black()
+                    )),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message(format!("declared '{}' again here", name.bright_black())),
+                ]
+            }
+
+            (Error::IncompleteType(ty), span) => {
+                let reason = match ty.is_void() {
+                    true => "can never be completed",
+                    false => "is not defined at this point",
+                };
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "'{}' {}",
+                        ty.bright_black(),
+                        reason
+                    )),
+                ]
+            }
+
+            (Error::InvalidMemberAccess(ty), span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message(format!("this has type '{}'", ty.bright_black()))]
+            }
+
+            (Error::UnknownMember(name, ty), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "'{}' has no member named '{}'",
+                        ty.bright_black(),
+                        name.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::InvalidCast(from, to), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "cannot cast '{}' to '{}'",
+                        from.bright_black(),
+                        to.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::InvalidVaList(ty), span)
+            | (Error::InvalidVaArg(ty), span)
+            | (Error::InvalidOperand(ty), span)
+            | (Error::NonScalarCondition(ty), span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message(format!("this has type '{}'", ty.bright_black()))]
+            }
+
+            (Error::VaStartOutsideVariadic, span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message("the function has no '...' in its parameters")]
+            }
+
+            (Error::NotLastParam(last), span) => {
+                let message = match last {
+                    Some(last) => format!("expected '{}'", last.bright_black()),
+                    None => "the last parameter has no name".to_string(),
+                };
+                vec![Label::new((src_id.clone(), span.into_range())).with_message(message)]
+            }
+
+            (Error::IncompatibleTypes(expected, found), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "expected '{}', found '{}'",
+                        expected.bright_black(),
+                        found.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::InvalidOperands(lhs, rhs), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "the operands have types '{}' and '{}'",
+                        lhs.bright_black(),
+                        rhs.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::ConflictingFunction(name, initial_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range())).with_message(format!(
+                        "function '{}' initially declared here",
+                        name.bright_black()
+                    )),
+                    Label::new((src_id, err_span.into_range())).with_message(format!(
+                        "declared '{}' with a different type here",
+                        name.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::RedefinedFunction(name, initial_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range())).with_message(format!(
+                        "function '{}' initially defined here",
+                        name.bright_black()
+                    )),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message(format!("defined '{}' again here", name.bright_black())),
+                ]
+            }
+
+            (Error::ConflictingLinkage(name, initial_span, external), err_span) => {
+                let linkage = |external| match external {
+                    true => "external",
+                    false => "internal",
+                };
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range())).with_message(format!(
+                        "'{}' first declared with {} linkage here",
+                        name.bright_black(),
+                        linkage(external)
+                    )),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message(format!("declared with {} linkage here", linkage(!external))),
+                ]
+            }
+
+            (Error::ArgumentCount(name, expected, decl_span), err_span) => {
+                let params = match expected {
+                    1 => "1 parameter".to_string(),
+                    n => format!("{} parameters", n),
+                };
+                let message = match name {
+                    Some(name) => format!(
+                        "function '{}' declared with {} here",
+                        name.bright_black(),
+                        params
+                    ),
+                    None => format!("this points to a function with {}", params),
+                };
+                vec![
+                    Label::new((src_id.clone(), decl_span.into_range())).with_message(message),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message("called with a different number of arguments here"),
+                ]
+            }
+
+            (Error::InvalidArgument(expected, found, param_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), param_span.into_range())).with_message(format!(
+                        "parameter declared as '{}' here",
+                        expected.bright_black()
+                    )),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message(format!("argument has type '{}'", found.bright_black())),
+                ]
+            }
+
+            (Error::InvalidSwitch(ty), span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message(format!("this has type '{}'", ty.bright_black()))]
+            }
+
+            (Error::CaseOutsideSwitch, span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message("there is no switch for this to belong to")]
+            }
+
+            (Error::DuplicateCase(value, initial_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range())).with_message(format!(
+                        "case '{}' initially used here",
+                        value.bright_black()
+                    )),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message(format!("used '{}' again here", value.bright_black())),
+                ]
+            }
+
+            (Error::DuplicateDefault(initial_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range()))
+                        .with_message("default label initially used here"),
+                    Label::new((src_id, err_span.into_range())).with_message("used again here"),
+                ]
+            }
+
+            (Error::NotConstant | Error::NotConstantInitializer, span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message("this cannot be evaluated at compile time")]
+            }
+
+            (Error::InvalidArrayLength(len), span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message(format!("length evaluates to {}", len.bright_black()))]
+            }
+
+            (Error::UndefinedLabel(name), span) => {
+                vec![
+                    Label::new((src_id.clone(), span.into_range())).with_message(format!(
+                        "'{}' is not a label in this function",
+                        name.bright_black()
+                    )),
+                ]
+            }
+
+            (Error::DuplicateLabel(name, initial_span), err_span) => {
+                vec![
+                    Label::new((src_id.clone(), initial_span.into_range())).with_message(format!(
+                        "label '{}' initially defined here",
+                        name.bright_black()
+                    )),
+                    Label::new((src_id, err_span.into_range()))
+                        .with_message(format!("defined '{}' again here", name.bright_black())),
+                ]
+            }
+
+            (Error::UnusedLabel(name), span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message(format!("no goto jumps to '{}'", name.bright_black()))]
+            }
+
+            (Error::BreakOutsideLoop, span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message("there is no loop to break out of")]
+            }
+
+            (Error::ContinueOutsideLoop, span) => {
+                vec![Label::new((src_id.clone(), span.into_range()))
+                    .with_message("there is no loop to continue")]
+            }
         }
         .into_iter()
         .map(|label| label.with_color(Color::Red))
         .collect()
     }
 }
+/// Copies `size` bytes from the address in `%rax` to the address in `%rcx`, using `%rdx`
+pub fn emit_copy(em: &mut Emitter, size: isize) {
+    let mut offset = 0;
+    for (chunk, mov, reg) in [
+        (8, "movq", "%rdx"),
+        (4, "movl", "%edx"),
+        (2, "movw", "%dx"),
+        (1, "movb", "%dl"),
+    ] {
+        while size - offset >= chunk {
+            em.emit_instr(&format!("{} {}(%rax), {}", mov, offset, reg));
+            em.emit_instr(&format!("{} {}, {}(%rcx)", mov, reg, offset));
+            offset += chunk;
+        }
+    }
+}
+
+/// Loads the value of type `ty` stored at `src` into `%rax`, extending it to 64 bits
+pub fn emit_load_scalar(em: &mut Emitter, ty: &Type, src: &str) {
+    em.emit_instr(&match ty.unqualified() {
+        Type::Char { signed: true } => format!("movsbq {}, %rax", src),
+        Type::Char { signed: false } => format!("movzbq {}, %rax", src),
+        Type::Short { signed: true } => format!("movswq {}, %rax", src),
+        Type::Short { signed: false } => format!("movzwq {}, %rax", src),
+        Type::Int { signed: true } => format!("movslq {}, %rax", src),
+        // Writing a 32-bit register clears the upper half
+        Type::Int { signed: false } | Type::Float => format!("mov {}, %eax", src),
+        _ => format!("mov {}, %rax", src),
+    });
+}
+
+/// Stores the value of type `ty` held in `%rax` to `dst`
+pub fn emit_store_scalar(em: &mut Emitter, ty: &Type, dst: &str) {
+    let reg = match ty.size() {
+        1 => "%al",
+        2 => "%ax",
+        4 => "%eax",
+        _ => "%rax",
+    };
+    em.emit_instr(&format!("mov {}, {}", reg, dst));
+}
+
+/// Converts the value in `%rax` to `ty`, truncating it and extending it back to 64 bits.
+/// Values are always kept extended this way, so that 64-bit instructions give the right
+/// result for any integer type.
+pub fn emit_convert(em: &mut Emitter, ty: &Type) {
+    match ty.unqualified() {
+        Type::Char { signed: true } => em.emit_instr("movsbq %al, %rax"),
+        Type::Char { signed: false } => em.emit_instr("movzbq %al, %rax"),
+        Type::Short { signed: true } => em.emit_instr("movswq %ax, %rax"),
+        Type::Short { signed: false } => em.emit_instr("movzwq %ax, %rax"),
+        Type::Int { signed: true } => em.emit_instr("movslq %eax, %rax"),
+        Type::Int { signed: false } => em.emit_instr("mov %eax, %eax"),
+        _ => {}
+    }
+}
+
+/// Converts the scalar in `%rax` from type `from` to type `to`. Floating point values are
+/// held in `%rax` as their bits. Clobbers `%rcx`, `%xmm0` and `%xmm1`.
+pub fn emit_cast(em: &mut Emitter, lt: &mut LabelTracker, from: &Type, to: &Type) {
+    let (from, to) = (from.unqualified(), to.unqualified());
+    match (from.is_float(), to.is_float()) {
+        (false, false) => emit_convert(em, to),
+
+        (true, true) if from == to => {}
+        (true, true) => {
+            em.emit_instr("movq %rax, %xmm0");
+            em.emit_instr(match to {
+                Type::Float => "cvtsd2ss %xmm0, %xmm0",
+                _ => "cvtss2sd %xmm0, %xmm0",
+            });
+            emit_from_xmm0(em, to);
+        }
+
+        // Integers are converted as signed 64-bit values, which every one of them but an
+        // unsigned 64-bit value with its top bit set fits in. Such a value is halved first,
+        // keeping its lowest bit for rounding, then doubled again.
+        (false, true) => {
+            let cvt = format!("cvtsi2{}q", sse_suffix(to));
+            if from.is_signed() || from.size() < 8 {
+                em.emit_instr(&format!("{} %rax, %xmm0", cvt));
+            } else {
+                let (large, end) = (
+                    lt.create(LabelKind::CastLarge),
+                    lt.create(LabelKind::CastEnd),
+                );
+                em.emit_instr("test %rax, %rax");
+                em.emit_instr(&format!("js {}", large));
+                em.emit_instr(&format!("{} %rax, %xmm0", cvt));
+                em.emit_instr(&format!("jmp {}", end));
+                em.emit_label(&large);
+                em.emit_instr("mov %rax, %rcx");
+                em.emit_instr("shr %rcx");
+                em.emit_instr("and $1, %eax");
+                em.emit_instr("or %rax, %rcx");
+                em.emit_instr(&format!("{} %rcx, %xmm0", cvt));
+                em.emit_instr(&format!("add{} %xmm0, %xmm0", sse_suffix(to)));
+                em.emit_label(&end);
+            }
+            emit_from_xmm0(em, to);
+        }
+
+        // Values of 2^63 and above only fit an unsigned 64-bit integer, and are brought in
+        // range by subtracting 2^63 before converting, then adding it back
+        (true, false) => {
+            let cvt = format!("cvtt{}2si", sse_suffix(from));
+            em.emit_instr("movq %rax, %xmm0");
+            if to.is_signed() || to.size() < 8 {
+                em.emit_instr(&format!("{} %xmm0, %rax", cvt));
+            } else {
+                let (large, end) = (
+                    lt.create(LabelKind::CastLarge),
+                    lt.create(LabelKind::CastEnd),
+                );
+                let two_63 = match from {
+                    Type::Float => (2f32.powi(63).to_bits() as u64).to_string(),
+                    _ => 2f64.powi(63).to_bits().to_string(),
+                };
+                em.emit_instr(&format!("movabs ${}, %rcx", two_63));
+                em.emit_instr("movq %rcx, %xmm1");
+                em.emit_instr(&format!("ucomi{} %xmm1, %xmm0", sse_suffix(from)));
+                em.emit_instr(&format!("jae {}", large));
+                em.emit_instr(&format!("{} %xmm0, %rax", cvt));
+                em.emit_instr(&format!("jmp {}", end));
+                em.emit_label(&large);
+                em.emit_instr(&format!("sub{} %xmm1, %xmm0", sse_suffix(from)));
+                em.emit_instr(&format!("{} %xmm0, %rax", cvt));
+                em.emit_instr("btc $63, %rax");
+                em.emit_label(&end);
+            }
+            emit_convert(em, to);
+        }
+    }
+}
+
+/// Compares the scalar of type `ty` in `%rax` with zero, setting the flags for `je`/`jne`.
+/// The sign bit of a floating point value is shifted out first, as negative zero is zero.
+pub fn emit_cmp_zero(em: &mut Emitter, ty: &Type) {
+    match ty.unqualified() {
+        Type::Float => em.emit_instr("shl $33, %rax"),
+        Type::Double => em.emit_instr("shl $1, %rax"),
+        _ => {}
+    }
+    em.emit_instr("cmp $0, %rax");
+}
+
+/// Moves the floating point value of type `ty` in `%xmm0` into `%rax`
+pub fn emit_from_xmm0(em: &mut Emitter, ty: &Type) {
+    match ty.unqualified() {
+        Type::Float => em.emit_instr("movd %xmm0, %eax"),
+        _ => em.emit_instr("movq %xmm0, %rax"),
+    }
+}
+
+/// The suffix of scalar SSE instructions operating on `ty`
+pub fn sse_suffix(ty: &Type) -> &'static str {
+    match ty.unqualified() {
+        Type::Float => "ss",
+        _ => "sd",
+    }
+}
+
 pub trait Codegen<'src> {
     fn code_gen(
         self,
@@ -0,0 +1,312 @@
+use super::{
+    env::Symbol,
+    expr::complete_type,
+    ty::{check_operand, check_operands},
+    BinaryOperator, CodegenError, Environment, Expr, Spanned, Type, TypeOf, UnaryOperator,
+};
+
+pub trait ConstEval<'src> {
+    /// Evaluates an integer constant expression at compile time
+    fn eval(&self, env: &Environment<'src>) -> Result<i64, Spanned<CodegenError<'src>>>;
+
+    /// Evaluates an arithmetic constant expression as a floating point value
+    fn eval_float(&self, env: &Environment<'src>) -> Result<f64, Spanned<CodegenError<'src>>>;
+}
+
+impl<'src> ConstEval<'src> for Spanned<Expr<'src>> {
+    fn eval(&self, env: &Environment<'src>) -> Result<i64, Spanned<CodegenError<'src>>> {
+        let (expr, span) = self;
+        let not_constant = (CodegenError::NotConstant, *span);
+        match expr {
+            Expr::Unary(op, rhs) => check_operand(*op, rhs, *span, env)?,
+            Expr::Binary(lhs, op, rhs) => check_operands(*op, lhs, rhs, *span, env)?,
+            _ => {}
+        }
+
+        Ok(match expr {
+            Expr::LiteralInteger(i, _) => *i as i64,
+
+            Expr::Variable(name) => match env.get_symbol(name) {
+                Some(Symbol::Enumerator(value, _)) => value,
+                Some(_) => return Err(not_constant),
+                None => return Err((CodegenError::UndeclaredVariable(name), *span)),
+            },
+
+            Expr::Unary(op, rhs) => {
+                let rhs = rhs.eval(env)?;
+                match op {
+                    UnaryOperator::Plus => rhs,
+                    UnaryOperator::Minus => rhs.wrapping_neg(),
+                    UnaryOperator::LogicalNot => (rhs == 0) as i64,
+                    UnaryOperator::BitwiseNot => !rhs,
+                    UnaryOperator::Deref
+                    | UnaryOperator::AddressOf
+                    | UnaryOperator::PreIncrement
+                    | UnaryOperator::PreDecrement
+                    | UnaryOperator::PostIncrement
+                    | UnaryOperator::PostDecrement => return Err(not_constant),
+                }
+            }
+
+            // The right hand side of a short circuited operator need not be constant
+            Expr::Binary(lhs, BinaryOperator::LogicalAnd, rhs) => {
+                (lhs.eval(env)? != 0 && rhs.eval(env)? != 0) as i64
+            }
+            Expr::Binary(lhs, BinaryOperator::LogicalOr, rhs) => {
+                (lhs.eval(env)? != 0 || rhs.eval(env)? != 0) as i64
+            }
+
+            Expr::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(env)?, rhs.eval(env)?);
+                match op {
+                    BinaryOperator::Plus => lhs.wrapping_add(rhs),
+                    BinaryOperator::Minus => lhs.wrapping_sub(rhs),
+                    BinaryOperator::Multiply => lhs.wrapping_mul(rhs),
+                    BinaryOperator::Divide | BinaryOperator::Mod if rhs == 0 => {
+                        return Err(not_constant)
+                    }
+                    BinaryOperator::Divide => lhs.wrapping_div(rhs),
+                    BinaryOperator::Mod => lhs.wrapping_rem(rhs),
+                    BinaryOperator::EqEq => (lhs == rhs) as i64,
+                    BinaryOperator::Ne => (lhs != rhs) as i64,
+                    BinaryOperator::Gt => (lhs > rhs) as i64,
+                    BinaryOperator::Lt => (lhs < rhs) as i64,
+                    BinaryOperator::Ge => (lhs >= rhs) as i64,
+                    BinaryOperator::Le => (lhs <= rhs) as i64,
+                    BinaryOperator::BitwiseAnd => lhs & rhs,
+                    BinaryOperator::BitwiseOr => lhs | rhs,
+                    BinaryOperator::BitwiseXor => lhs ^ rhs,
+                    BinaryOperator::LeftShift => lhs.wrapping_shl(rhs as u32),
+                    BinaryOperator::RightShift => lhs.wrapping_shr(rhs as u32),
+                    _ => return Err(not_constant),
+                }
+            }
+
+            Expr::Ternary(cond, a, b) => match cond.eval(env)? {
+                0 => b.eval(env)?,
+                _ => a.eval(env)?,
+            },
+
+            Expr::SizeOf(rhs) => rhs.ty(env)?.size() as i64,
+            Expr::SizeOfType(ty) => complete_type(ty, env, *span)?.size() as i64,
+            Expr::AlignOf(ty) => complete_type(ty, env, *span)?.align() as i64,
+
+            // Float operands are truncated toward zero, as at runtime
+            Expr::Cast(ty, rhs) => {
+                let to = ty.lookup(env, *span)?;
+                let i = match rhs.ty(env)?.is_float() {
+                    true => match to.is_signed() {
+                        true => rhs.eval_float(env)? as i64,
+                        false => rhs.eval_float(env)? as u64 as i64,
+                    },
+                    false => rhs.eval(env)?,
+                };
+                match to.is_integer() {
+                    true => to.truncate(i),
+                    false => return Err(not_constant),
+                }
+            }
+
+            _ => return Err(not_constant),
+        })
+    }
+
+    fn eval_float(&self, env: &Environment<'src>) -> Result<f64, Spanned<CodegenError<'src>>> {
+        let (expr, span) = self;
+        let not_constant = (CodegenError::NotConstant, *span);
+
+        // Integer operands are evaluated exactly, then converted
+        let ty = self.ty(env)?;
+        if !ty.is_float() {
+            let i = self.eval(env)?;
+            return Ok(match ty.is_signed() {
+                true => i as f64,
+                false => i as u64 as f64,
+            });
+        }
+
+        let f = match expr {
+            Expr::LiteralFloat(f, _) => *f,
+
+            Expr::Unary(UnaryOperator::Plus, rhs) => rhs.eval_float(env)?,
+            Expr::Unary(UnaryOperator::Minus, rhs) => -rhs.eval_float(env)?,
+            Expr::Cast(_, rhs) => rhs.eval_float(env)?,
+
+            Expr::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval_float(env)?, rhs.eval_float(env)?);
+                match op {
+                    BinaryOperator::Plus => lhs + rhs,
+                    BinaryOperator::Minus => lhs - rhs,
+                    BinaryOperator::Multiply => lhs * rhs,
+                    BinaryOperator::Divide => lhs / rhs,
+                    _ => return Err(not_constant),
+                }
+            }
+
+            Expr::Ternary(cond, a, b) => match cond.eval_float(env)? {
+                0.0 => b.eval_float(env)?,
+                _ => a.eval_float(env)?,
+            },
+
+            _ => return Err(not_constant),
+        };
+
+        // Every operation on floats is rounded to float
+        Ok(match ty {
+            Type::Float => f as f32 as f64,
+            _ => f,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{Span, TypeSpec};
+
+    fn lit(i: u64) -> Spanned<Expr<'static>> {
+        (
+            Expr::LiteralInteger(i, Type::Int { signed: true }),
+            Span::new(0, 1),
+        )
+    }
+
+    fn var(name: &str) -> Spanned<Expr<'_>> {
+        (Expr::Variable(name), Span::new(0, 1))
+    }
+
+    #[test]
+    fn arithmetic() {
+        let env = Environment::new();
+        let span = Span::new(0, 1);
+
+        let expr = Expr::new_binary(
+            lit(2),
+            BinaryOperator::Plus,
+            Expr::new_binary(lit(3), BinaryOperator::Multiply, lit(4), span),
+            span,
+        );
+        assert_eq!(expr.eval(&env).ok(), Some(14));
+
+        let expr = Expr::new_unary(UnaryOperator::Minus, lit(1), span);
+        assert_eq!(expr.eval(&env).ok(), Some(-1));
+
+        let expr = Expr::new_binary(lit(1), BinaryOperator::LeftShift, lit(4), span);
+        assert_eq!(expr.eval(&env).ok(), Some(16));
+
+        let expr = Expr::new_ternary(lit(0), lit(1), lit(2), span);
+        assert_eq!(expr.eval(&env).ok(), Some(2));
+    }
+
+    #[test]
+    fn not_constant() {
+        let mut env = Environment::new();
+        let span = Span::new(0, 1);
+        env.new_scope();
+        env.put("x", Type::Int { signed: true }, span);
+
+        assert!(matches!(
+            var("x").eval(&env),
+            Err((CodegenError::NotConstant, _))
+        ));
+        assert!(matches!(
+            var("y").eval(&env),
+            Err((CodegenError::UndeclaredVariable("y"), _))
+        ));
+
+        let expr = Expr::new_binary(lit(1), BinaryOperator::Divide, lit(0), span);
+        assert!(matches!(
+            expr.eval(&env),
+            Err((CodegenError::NotConstant, _))
+        ));
+    }
+
+    #[test]
+    fn enumerators() {
+        let mut env = Environment::new();
+        let span = Span::new(0, 1);
+        env.new_scope();
+        env.put_enumerator("A", 7, span);
+
+        let expr = Expr::new_binary(var("A"), BinaryOperator::Plus, lit(1), span);
+        assert_eq!(expr.eval(&env).ok(), Some(8));
+
+        // Shadowed by a variable in an inner scope
+        env.new_scope();
+        env.put("A", Type::Int { signed: true }, span);
+        assert!(matches!(
+            var("A").eval(&env),
+            Err((CodegenError::NotConstant, _))
+        ));
+    }
+
+    #[test]
+    fn casts() {
+        let env = Environment::new();
+        let span = Span::new(0, 1);
+        let cast = |ty, rhs| Expr::new_cast(ty, rhs, span);
+
+        let expr = cast(TypeSpec::Char { signed: true }, lit(300));
+        assert_eq!(expr.eval(&env).ok(), Some(44));
+
+        let expr = cast(
+            TypeSpec::Short { signed: false },
+            Expr::new_unary(UnaryOperator::Minus, lit(1), span),
+        );
+        assert_eq!(expr.eval(&env).ok(), Some(65535));
+
+        let expr = cast(
+            TypeSpec::Int { signed: true },
+            (Expr::LiteralFloat(-2.5, Type::Double), span),
+        );
+        assert_eq!(expr.eval(&env).ok(), Some(-2));
+
+        let expr = (Expr::SizeOfType(TypeSpec::Long { signed: true }), span);
+        assert_eq!(expr.eval(&env).ok(), Some(8));
+    }
+
+    #[test]
+    fn floats() {
+        let env = Environment::new();
+        let span = Span::new(0, 1);
+        let float = |f: f64, ty: Type| (Expr::LiteralFloat(f, ty), span);
+
+        let expr = Expr::new_binary(
+            float(1.5, Type::Double),
+            BinaryOperator::Multiply,
+            lit(3),
+            span,
+        );
+        assert_eq!(expr.eval_float(&env).ok(), Some(4.5));
+
+        let expr = Expr::new_unary(UnaryOperator::Minus, float(0.5, Type::Double), span);
+        assert_eq!(expr.eval_float(&env).ok(), Some(-0.5));
+
+        // Rounded to float after each operation
+        let third = 1.0 / 3.0;
+        let expr = Expr::new_binary(
+            float(1.0, Type::Float),
+            BinaryOperator::Divide,
+            float(3.0, Type::Float),
+            span,
+        );
+        assert_eq!(expr.eval_float(&env).ok(), Some(third as f32 as f64));
+
+        let expr = (
+            Expr::LiteralInteger(u64::MAX, Type::Long { signed: false }),
+            span,
+        );
+        assert_eq!(expr.eval_float(&env).ok(), Some(u64::MAX as f64));
+
+        let expr = Expr::new_binary(
+            float(1.0, Type::Double),
+            BinaryOperator::Mod,
+            float(3.0, Type::Double),
+            span,
+        );
+        assert!(matches!(
+            expr.eval_float(&env),
+            Err((CodegenError::InvalidOperands(Type::Double, Type::Double), _))
+        ));
+    }
+}
@@ -1,12 +1,112 @@
 use super::{
-    emitter::Emitter, label_tracker::LabelTracker, Codegen, CodegenError, Environment, Spanned,
-    Stmt,
+    abi::{
+        assign_registers, classify, emit_store_eightbyte, ArgClass, ARG_REGISTERS,
+        SSE_ARG_REGISTERS,
+    },
+    emitter::Emitter,
+    env::{slot_size, Function, Location, RegSaveArea, Symbol, WORD_IN_BYTES},
+    expr::converts,
+    init::{array_length, lower, Initializer},
+    label_tracker::{LabelKind, LabelTracker},
+    ty::{align_to, member_of, Qualifiers},
+    type_spec::{resolve_array, resolve_function},
+    BinaryOperator, Codegen, CodegenError, ConstEval, Environment, Expr, Spanned, Stmt, Type,
+    TypeOf, TypeSpec, UnaryOperator,
 };
 
+/// A declaration at file scope
 #[derive(Debug)]
-pub struct FnDeclaration<'src>(pub &'src str, pub Vec<Spanned<Stmt<'src>>>);
+pub enum Decl<'src> {
+    Function(FnDeclaration<'src>),
+    /// Variables with static storage, whose initializers must be constant
+    Variable(Option<StorageClass>, TypeSpec<'src>, Vec<Declarator<'src>>),
+    Type(TypeSpec<'src>),
+    Typedef(TypeSpec<'src>, Spanned<&'src str>),
+}
+
+/// Where a declared object lives and which translation units can see it. Without one, a
+/// local has automatic storage and anything at file scope is visible to every translation
+/// unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageClass {
+    Static,
+    Extern,
+}
+
+/// One of the names a declaration declares, as in `*p` and `a[2] = ...` of
+/// `int *p, a[2] = ...;`. The pointers and array lengths apply to the type the declaration
+/// starts with, which is only resolved once for all of its declarators.
+#[derive(Debug)]
+pub struct Declarator<'src> {
+    /// The qualifiers of each pointer, as in `* const`, in the order they are written
+    pub pointers: Vec<Qualifiers>,
+    /// For a parenthesised declarator like `(*f)(int)`, the pointers inside the parentheses,
+    /// and the parameters of the function type they point to and whether it is variadic
+    pub function: Option<(Vec<Qualifiers>, Vec<TypeSpec<'src>>, bool)>,
+    pub name: Spanned<&'src str>,
+    /// Array lengths, outermost first
+    pub dims: Vec<Spanned<Expr<'src>>>,
+    /// Whether the outermost array length is left out, as in `a[]`, to be counted from the
+    /// initializer
+    pub unknown_length: bool,
+    pub init: Option<Spanned<Initializer<'src>>>,
+}
 
-impl<'src> Codegen<'src> for Vec<Spanned<FnDeclaration<'src>>> {
+impl<'src> Declarator<'src> {
+    /// The type of the declared name, given the type its declaration starts with
+    pub fn resolve(
+        &self,
+        base: &Type,
+        env: &Environment<'src>,
+    ) -> Result<Type, Spanned<CodegenError<'src>>> {
+        let mut ty = pointers_to(base.clone(), &self.pointers);
+        if let Some((pointers, params, variadic)) = &self.function {
+            ty = resolve_function(ty, params, *variadic, env, self.name.1)?;
+            ty = pointers_to(ty, pointers);
+        }
+        let ty = self
+            .dims
+            .iter()
+            .rev()
+            .try_fold(ty, |ty, len| resolve_array(ty, len, env, self.name.1))?;
+        if !self.unknown_length {
+            return Ok(ty);
+        }
+
+        if !ty.is_complete() {
+            return Err((CodegenError::IncompleteType(ty), self.name.1));
+        }
+        let len = match &self.init {
+            Some(init) => array_length(&ty, init, env)?,
+            None => None,
+        };
+        match len {
+            Some(len) => Ok(ty.array_of(len)),
+            None => Err((CodegenError::MissingArrayLength(self.name.0), self.name.1)),
+        }
+    }
+}
+
+/// A pointer to `ty` for each of `pointers`, outermost last, qualified as each is
+fn pointers_to(ty: Type, pointers: &[Qualifiers]) -> Type {
+    pointers
+        .iter()
+        .fold(ty, |ty, qualifiers| ty.pointer_to().qualified(*qualifiers))
+}
+
+/// A function definition, or a prototype without a body
+#[derive(Debug)]
+pub struct FnDeclaration<'src>(
+    pub Option<StorageClass>,
+    pub TypeSpec<'src>,
+    pub Spanned<&'src str>,
+    pub Vec<(TypeSpec<'src>, Spanned<Option<&'src str>>)>,
+    /// Whether the parameters end with `...`
+    pub bool,
+    pub Option<Vec<Spanned<Stmt<'src>>>>,
+);
+
+impl<'src> Codegen<'src> for Vec<Spanned<Decl<'src>>> {
     fn code_gen(
         self,
         lt: &mut LabelTracker,
@@ -16,10 +116,279 @@ impl<'src> Codegen<'src> for Vec<Spanned<FnDeclaration<'src>>> {
         self.into_iter()
             .map(|decl| decl.code_gen(lt, em, env))
             .collect::<Result<Vec<_>, _>>()?;
+
+        // Variables never given an initializer are zero
+        for (name, var, external) in env.tentative_globals() {
+            em.emit_bss(name, var.ty.size(), var.ty.align(), external);
+        }
         Ok(())
     }
 }
 
+impl<'src> Codegen<'src> for Spanned<Decl<'src>> {
+    fn code_gen(
+        self,
+        lt: &mut LabelTracker,
+        em: &mut Emitter,
+        env: &mut Environment<'src>,
+    ) -> Result<(), Spanned<CodegenError<'src>>> {
+        match self {
+            (Decl::Function(decl), span) => (decl, span).code_gen(lt, em, env),
+
+            (Decl::Variable(storage, ty, declarators), span) => {
+                let base = ty.resolve(env, span)?;
+                for declarator in declarators {
+                    let ty = declarator.resolve(&base, env)?;
+                    declare_global(lt, em, env, storage, ty, declarator.name, declarator.init)?;
+                }
+                Ok(())
+            }
+
+            (Decl::Type(ty), span) => ty.resolve(env, span).map(|_| ()),
+            (Decl::Typedef(ty, name), span) => {
+                (Stmt::Typedef(ty, name), span).code_gen(lt, em, env)
+            }
+        }
+    }
+}
+
+/// Declares the file scope variable `name`, defining it if it has an initializer
+fn declare_global<'src>(
+    lt: &mut LabelTracker,
+    em: &mut Emitter,
+    env: &mut Environment<'src>,
+    storage: Option<StorageClass>,
+    ty: Type,
+    (name, name_span): Spanned<&'src str>,
+    init: Option<Spanned<Initializer<'src>>>,
+) -> Result<(), Spanned<CodegenError<'src>>> {
+    if !ty.is_complete() && storage != Some(StorageClass::Extern) {
+        return Err((CodegenError::IncompleteType(ty), name_span));
+    }
+
+    // A file scope variable may be declared again with the same type. It is declared before
+    // its initializer, which may take its address.
+    let span = match env.get_in_scope(name) {
+        Some(Symbol::Variable(var)) if var.ty == ty => var.span,
+        Some(Symbol::Variable(var)) => {
+            return Err((CodegenError::IncompatibleTypes(var.ty, ty), name_span))
+        }
+        Some(symbol) => {
+            return Err((
+                CodegenError::RedeclaredVariable(name, symbol.span()),
+                name_span,
+            ))
+        }
+        None => {
+            env.put_global(name, name.to_owned(), ty.clone(), name_span);
+            name_span
+        }
+    };
+
+    // The first declaration decides whether the variable is visible to other translation
+    // units. A later `extern` keeps that, but no other declaration may change it.
+    let global = *env.linkage_mut(name, storage != Some(StorageClass::Static));
+    let external = match storage {
+        Some(StorageClass::Static) => false,
+        Some(StorageClass::Extern) => global.external,
+        None => true,
+    };
+    if external != global.external {
+        return Err((
+            CodegenError::ConflictingLinkage(name, span, global.external),
+            name_span,
+        ));
+    }
+    match init {
+        Some(_) if global.defined => {
+            return Err((CodegenError::RedeclaredVariable(name, span), name_span))
+        }
+        Some(init) => {
+            let values = static_data(lt, em, env, &ty, &init)?;
+            em.emit_data(name, ty.align(), &values, global.external);
+            env.linkage_mut(name, global.external).defined = true;
+        }
+        None if storage == Some(StorageClass::Extern) => {}
+        None => env.linkage_mut(name, global.external).tentative = true,
+    }
+    Ok(())
+}
+
+/// The directives holding an object of type `ty` initialized by `init`, which is zero
+/// wherever the initializer stores nothing
+pub fn static_data<'src>(
+    lt: &mut LabelTracker,
+    em: &mut Emitter,
+    env: &Environment<'src>,
+    ty: &Type,
+    init: &Spanned<Initializer<'src>>,
+) -> Result<Vec<String>, Spanned<CodegenError<'src>>> {
+    let mut values = Vec::new();
+    let mut offset = 0;
+    for element in lower(ty, init, env)? {
+        if element.offset > offset {
+            values.push(format!(".zero {}", element.offset - offset));
+        }
+        // Copying a struct is never constant
+        if element.ty.is_aggregate() {
+            return Err((CodegenError::NotConstantInitializer, element.expr.1));
+        }
+        let init_ty = element.expr.ty(env)?.decay();
+        if !converts(&element.ty, &init_ty, &element.expr, env) {
+            return Err((
+                CodegenError::IncompatibleTypes(element.ty, init_ty),
+                element.expr.1,
+            ));
+        }
+        values.push(static_value(lt, em, env, &element.ty, &element.expr)?);
+        offset = element.offset + element.ty.size();
+    }
+    if ty.size() > offset {
+        values.push(format!(".zero {}", ty.size() - offset));
+    }
+    Ok(values)
+}
+
+/// The directive holding the constant `init` converted to the scalar type `ty`
+fn static_value<'src>(
+    lt: &mut LabelTracker,
+    em: &mut Emitter,
+    env: &Environment<'src>,
+    ty: &Type,
+    init: &Spanned<Expr<'src>>,
+) -> Result<String, Spanned<CodegenError<'src>>> {
+    constant_value(lt, em, env, ty, init).map_err(|err| match err {
+        (CodegenError::NotConstant, _) => (CodegenError::NotConstantInitializer, init.1),
+        err => err,
+    })
+}
+
+fn constant_value<'src>(
+    lt: &mut LabelTracker,
+    em: &mut Emitter,
+    env: &Environment<'src>,
+    ty: &Type,
+    init: &Spanned<Expr<'src>>,
+) -> Result<String, Spanned<CodegenError<'src>>> {
+    let init_ty = init.ty(env)?.decay();
+
+    if ty.is_pointer() && init_ty.is_pointer() {
+        if let Some((label, offset)) = address_constant(lt, em, env, init)? {
+            return Ok(match offset {
+                0 => format!(".quad {}", label),
+                _ => format!(".quad {}{:+}", label, offset),
+            });
+        }
+    }
+
+    Ok(match ty.unqualified() {
+        Type::Float => format!(".long {}", (init.eval_float(env)? as f32).to_bits()),
+        Type::Double => format!(".quad {}", init.eval_float(env)?.to_bits()),
+        _ => {
+            let i = match init_ty {
+                Type::Float | Type::Double => {
+                    let f = init.eval_float(env)?;
+                    match ty.is_signed() {
+                        true => f as i64,
+                        false => f as u64 as i64,
+                    }
+                }
+                _ => init.eval(env)?,
+            };
+            match ty.size() {
+                1 => format!(".byte {}", i as u8),
+                2 => format!(".short {}", i as u16),
+                4 => format!(".long {}", i as u32),
+                _ => format!(".quad {}", i as u64),
+            }
+        }
+    })
+}
+
+/// The label and byte offset of an address constant. That is a string literal, or the
+/// address of an object with static storage or of a function, which arrays and functions
+/// evaluate to, plus or minus an integer constant.
+fn address_constant<'src>(
+    lt: &mut LabelTracker,
+    em: &mut Emitter,
+    env: &Environment<'src>,
+    expr: &Spanned<Expr<'src>>,
+) -> Result<Option<(String, isize)>, Spanned<CodegenError<'src>>> {
+    let ty = expr.ty(env)?;
+    Ok(match &expr.0 {
+        Expr::LiteralString(bytes) => Some((
+            em.emit_string(bytes.clone(), || lt.create(LabelKind::String)),
+            0,
+        )),
+        Expr::Variable(name) if ty.is_function() => global_label(env, name).map(|label| (label, 0)),
+        _ if ty.is_array() => object_address(lt, em, env, expr)?,
+        Expr::Unary(UnaryOperator::AddressOf, rhs) => object_address(lt, em, env, rhs)?,
+        Expr::Cast(_, rhs) if ty.is_pointer() => address_constant(lt, em, env, rhs)?,
+        Expr::Binary(lhs, op @ (BinaryOperator::Plus | BinaryOperator::Minus), rhs) => {
+            let Some(pointee) = ty.pointee() else {
+                return Ok(None);
+            };
+            let (base, index, sign) = match lhs.ty(env)?.decay().is_pointer() {
+                true if *op == BinaryOperator::Minus => (lhs, rhs, -1),
+                true => (lhs, rhs, 1),
+                false => (rhs, lhs, 1),
+            };
+            match address_constant(lt, em, env, base)? {
+                Some((label, offset)) => {
+                    let index = index.eval(env)? as isize;
+                    Some((label, offset + sign * index * pointee.size()))
+                }
+                None => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// The label and byte offset of the object `expr` designates, if its address is constant
+fn object_address<'src>(
+    lt: &mut LabelTracker,
+    em: &mut Emitter,
+    env: &Environment<'src>,
+    expr: &Spanned<Expr<'src>>,
+) -> Result<Option<(String, isize)>, Spanned<CodegenError<'src>>> {
+    Ok(match &expr.0 {
+        Expr::Variable(name) => global_label(env, name).map(|label| (label, 0)),
+        Expr::Unary(UnaryOperator::Deref, rhs) => address_constant(lt, em, env, rhs)?,
+        // Either operand of `a[i]` may be the pointer
+        Expr::Index(lhs, rhs) => {
+            let (base, index) = match lhs.ty(env)?.decay().is_pointer() {
+                true => (lhs, rhs),
+                false => (rhs, lhs),
+            };
+            match address_constant(lt, em, env, base)? {
+                Some((label, offset)) => {
+                    let index = index.eval(env)? as isize;
+                    Some((label, offset + index * expr.ty(env)?.size()))
+                }
+                None => None,
+            }
+        }
+        Expr::Member(lhs, name) => match object_address(lt, em, env, lhs)? {
+            Some((label, offset)) => Some((label, offset + member_of(lhs, *name, env)?.offset)),
+            None => None,
+        },
+        _ => None,
+    })
+}
+
+/// The label of the variable `name` if it has static storage, or of the function `name`
+fn global_label(env: &Environment, name: &str) -> Option<String> {
+    match env.get_symbol(name) {
+        Some(Symbol::Variable(var)) => match var.location {
+            Location::Global(label) => Some(label),
+            Location::Local(_) => None,
+        },
+        Some(_) => None,
+        None => env.get_function(name).map(|_| name.to_owned()),
+    }
+}
+
 impl<'src> Codegen<'src> for Spanned<FnDeclaration<'src>> {
     fn code_gen(
         self,
@@ -27,18 +396,340 @@ impl<'src> Codegen<'src> for Spanned<FnDeclaration<'src>> {
         em: &mut Emitter,
         env: &mut Environment<'src>,
     ) -> Result<(), Spanned<CodegenError<'src>>> {
-        let (FnDeclaration(name, body), _) = self;
+        let (FnDeclaration(storage, ret, (name, name_span), params, variadic, body), span) = self;
 
+        let ret = ret.resolve(env, span)?.unqualified().clone();
+        env.sp = 0;
+        env.frame_size = 0;
+        env.ret = Some(ret.clone());
+        env.ret_ptr = None;
+        env.reg_save_area = None;
+        env.function = Some(name);
         env.new_scope();
 
-        em.emit_directive(&format!(".globl {}", name));
+        // Parameters declared as arrays through a typedef, such as a `va_list`, are pointers.
+        // Their qualifiers only apply within the body.
+        let params = params
+            .into_iter()
+            .map(|(ty, (param, span))| match ty.resolve(env, span)? {
+                // `(void)` declares that there are no parameters, so none can be `void`
+                ty if ty.is_void() => Err((CodegenError::IncompleteType(ty), span)),
+                ty => Ok((if ty.is_array() { ty.decay() } else { ty }, param, span)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Every declaration of a function must agree, and only one may define it
+        let mut function = Function {
+            ret: ret.clone(),
+            params: params
+                .iter()
+                .map(|(ty, _, span)| (ty.unqualified().clone(), *span))
+                .collect(),
+            variadic,
+            span: name_span,
+            definition: body.as_ref().map(|_| name_span),
+            external: storage != Some(StorageClass::Static),
+        };
+        if let Some(prev) = env.get_function(name) {
+            if prev.ret != function.ret || !prev.has_params(&function) {
+                return Err((
+                    CodegenError::ConflictingFunction(name, prev.span),
+                    name_span,
+                ));
+            }
+            if let (Some(def_span), Some(_)) = (prev.definition, &body) {
+                return Err((CodegenError::RedefinedFunction(name, def_span), name_span));
+            }
+            if !function.is_prototyped() {
+                function.params = prev.params.clone();
+                function.variadic = prev.variadic;
+            }
+            // Functions without `static` keep the linkage of their first declaration
+            if prev.external && !function.external {
+                return Err((
+                    CodegenError::ConflictingLinkage(name, prev.span, true),
+                    name_span,
+                ));
+            }
+            function.span = prev.span;
+            function.definition = function.definition.or(prev.definition);
+            function.external = prev.external;
+        }
+        let external = function.external;
+        env.put_function(name, function);
+
+        let Some(body) = body else {
+            env.end_scope();
+            return Ok(());
+        };
+
+        // Static functions are only visible in this translation unit
+        if external {
+            em.emit_directive(&format!(".globl {}", name));
+        }
         em.emit_label(name);
         em.emit_instr("push %rbp");
         em.emit_instr("mov %rsp, %rbp");
+        let prologue = em.position();
+
+        // A struct returned in memory is written to the address the caller passed in %rdi
+        let (mut ints, mut sses) = (0, 0);
+        if ret.is_aggregate() && classify(&ret) == [ArgClass::Memory] {
+            let ret_ptr = env.put_temp(&Type::Int { signed: true }.pointer_to());
+            em.emit_instr(&format!("mov %rdi, {}(%rbp)", ret_ptr));
+            env.ret_ptr = Some(ret_ptr);
+            ints += 1;
+        }
+
+        // Variable arguments may be in any argument register, so all of them are saved
+        // before the parameters are spilled. A definition with `()` takes no arguments.
+        let last_param = params.last().and_then(|(_, param, _)| *param);
+        let save_area = (variadic && !params.is_empty()).then(|| {
+            let size = (ARG_REGISTERS.len() + 2 * SSE_ARG_REGISTERS.len()) as isize * 8;
+            let offset = env.put_temp(&Type::Char { signed: true }.array_of(size as usize));
+            for (i, reg) in ARG_REGISTERS.iter().enumerate() {
+                em.emit_instr(&format!("mov {}, {}(%rbp)", reg, offset + i as isize * 8));
+            }
+            let fp_start = offset + ARG_REGISTERS.len() as isize * 8;
+            for (i, reg) in SSE_ARG_REGISTERS.iter().enumerate() {
+                em.emit_instr(&format!(
+                    "movq {}, {}(%rbp)",
+                    reg,
+                    fp_start + i as isize * 16
+                ));
+            }
+            offset
+        });
+
+        // Arguments that arrived in registers are spilled into the frame, the rest were
+        // pushed by the caller right above the return address
+        let mut stack_offset = 2 * WORD_IN_BYTES;
+        for (ty, param, span) in params {
+            // An unnamed parameter still takes up its register or stack slot
+            let Some(param) = param else {
+                let regs = assign_registers(
+                    &classify(&ty),
+                    (&ARG_REGISTERS, &mut ints),
+                    (&SSE_ARG_REGISTERS, &mut sses),
+                );
+                if regs.is_none() {
+                    stack_offset += slot_size(&ty);
+                }
+                continue;
+            };
+
+            match assign_registers(
+                &classify(&ty),
+                (&ARG_REGISTERS, &mut ints),
+                (&SSE_ARG_REGISTERS, &mut sses),
+            ) {
+                Some(regs) => {
+                    let declared = env.put(param, ty, span);
+                    let offset = env.sp;
+                    for (i, reg) in regs.into_iter().enumerate() {
+                        emit_store_eightbyte(em, reg, "%rbp", offset + i as isize * WORD_IN_BYTES);
+                    }
+                    declared
+                }
+                None => {
+                    let offset = stack_offset;
+                    stack_offset += slot_size(&ty);
+                    env.put_at(param, offset, ty, span)
+                }
+            }
+            .then_some(())
+            .ok_or_else(|| {
+                let init_span = env.get_in_scope(param).expect("infallible").span();
+                (CodegenError::RedeclaredVariable(param, init_span), span)
+            })?;
+        }
+
+        env.reg_save_area = save_area.map(|offset| RegSaveArea {
+            offset,
+            gp_offset: ints as isize * 8,
+            fp_offset: (ARG_REGISTERS.len() + 2 * sses) as isize * 8,
+            overflow: stack_offset,
+            last_param,
+        });
+
         body.code_gen(lt, em, env)?;
 
+        // Gotos may jump forwards, so they are only checked once every label is known
+        let (labels, gotos) = env.take_labels();
+        if let Some(&(label, span)) = gotos.iter().find(|(label, _)| !labels.contains_key(label)) {
+            return Err((CodegenError::UndefinedLabel(label), span));
+        }
+        let mut unused = labels
+            .into_iter()
+            .filter(|(label, _)| !gotos.iter().any(|(goto, _)| goto == label))
+            .collect::<Vec<_>>();
+        unused.sort_by_key(|(_, span)| span.start);
+        env.warnings.extend(
+            unused
+                .into_iter()
+                .map(|(label, span)| (CodegenError::UnusedLabel(label), span)),
+        );
+
+        // Falling off the end of a function returns 0
+        em.emit_instr("mov $0, %rax");
+        em.emit_instr("mov %rbp, %rsp");
+        em.emit_instr("pop %rbp");
+        em.emit_instr("ret");
+
         env.end_scope();
 
+        // Only now is the space needed by every local known
+        let frame_size = align_to(env.frame_size, 16);
+        if frame_size > 0 {
+            em.insert_instr(prologue, &format!("sub ${}, %rsp", frame_size));
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{
+        ty::{StructRef, TagKind},
+        Span,
+    };
+
+    fn var(name: &str) -> Spanned<Expr<'_>> {
+        (Expr::Variable(name), Span::new(0, 1))
+    }
+
+    fn lit(i: u64) -> Spanned<Expr<'static>> {
+        (
+            Expr::LiteralInteger(i, Type::Int { signed: true }),
+            Span::new(0, 1),
+        )
+    }
+
+    #[test]
+    fn address_constants() {
+        let (mut lt, mut em, mut env) = (LabelTracker::new(), Emitter::new(), Environment::new());
+        let int = Type::Int { signed: true };
+        let span = Span::new(0, 1);
+        let s = StructRef::new(TagKind::Struct, Some("s".into()));
+        s.define(vec![("x".into(), int.clone()), ("y".into(), int.clone())]);
+        env.put_global("g", "g".into(), int.clone().array_of(5), span);
+        env.put_global("s", "s".into(), Type::Struct(s), span);
+
+        let mut value =
+            |expr| static_value(&mut lt, &mut em, &env, &int.clone().pointer_to(), &expr);
+
+        // &g[2]
+        let expr = Expr::new_unary(
+            UnaryOperator::AddressOf,
+            Expr::new_index(var("g"), lit(2), span),
+            span,
+        );
+        assert_eq!(value(expr).ok().as_deref(), Some(".quad g+8"));
+
+        // 3 + g - 1
+        let expr = Expr::new_binary(
+            Expr::new_binary(lit(3), BinaryOperator::Plus, var("g"), span),
+            BinaryOperator::Minus,
+            lit(1),
+            span,
+        );
+        assert_eq!(value(expr).ok().as_deref(), Some(".quad g+8"));
+
+        // &s.y
+        let expr = Expr::new_unary(
+            UnaryOperator::AddressOf,
+            Expr::new_member(var("s"), ("y", span), span),
+            span,
+        );
+        assert_eq!(value(expr).ok().as_deref(), Some(".quad s+4"));
+
+        // g - 1
+        let expr = Expr::new_binary(var("g"), BinaryOperator::Minus, lit(1), span);
+        assert_eq!(value(expr).ok().as_deref(), Some(".quad g-4"));
+    }
+
+    #[test]
+    fn linkage() {
+        let span = Span::new(0, 1);
+        let int = || TypeSpec::Int { signed: true };
+        let variable = |storage, name| {
+            let declarator = Declarator {
+                pointers: vec![],
+                function: None,
+                name: (name, span),
+                dims: vec![],
+                unknown_length: false,
+                init: None,
+            };
+            (Decl::Variable(storage, int(), vec![declarator]), span)
+        };
+        let function = |storage, name, body| {
+            let params = vec![(int(), (Some("x"), span))];
+            let decl = FnDeclaration(storage, int(), (name, span), params, false, body);
+            (Decl::Function(decl), span)
+        };
+        let code_gen = |decls: Vec<Spanned<Decl<'static>>>| {
+            let mut em = Emitter::new();
+            decls
+                .code_gen(&mut LabelTracker::new(), &mut em, &mut Environment::new())
+                .map(|_| em.collect())
+        };
+
+        // A later `extern` or definition keeps the linkage of the first declaration
+        let asm = code_gen(vec![
+            variable(Some(StorageClass::Static), "h"),
+            variable(Some(StorageClass::Extern), "h"),
+            function(Some(StorageClass::Static), "f", None),
+            function(None, "f", Some(vec![])),
+        ])
+        .unwrap();
+        assert!(!asm.contains(".globl"));
+
+        for decls in [
+            vec![
+                variable(Some(StorageClass::Extern), "h"),
+                variable(Some(StorageClass::Static), "h"),
+            ],
+            vec![
+                variable(Some(StorageClass::Static), "h"),
+                variable(None, "h"),
+            ],
+            vec![
+                function(None, "f", None),
+                function(Some(StorageClass::Static), "f", Some(vec![])),
+            ],
+        ] {
+            assert!(matches!(
+                code_gen(decls),
+                Err((CodegenError::ConflictingLinkage("h" | "f", _, _), _))
+            ));
+        }
+    }
+
+    #[test]
+    fn void_returns() {
+        let span = Span::new(0, 1);
+        let function = |ret, value| {
+            let body = vec![(Stmt::Return(value), span)];
+            let decl = FnDeclaration(None, ret, ("f", span), vec![], false, Some(body));
+            (decl, span).code_gen(
+                &mut LabelTracker::new(),
+                &mut Emitter::new(),
+                &mut Environment::new(),
+            )
+        };
+
+        // Only a function returning `void` returns without a value, and it has none to return
+        assert!(function(TypeSpec::Void, None).is_ok());
+        assert!(matches!(
+            function(TypeSpec::Void, Some(lit(1))),
+            Err((CodegenError::IncompatibleTypes(Type::Void, _), _))
+        ));
+        assert!(matches!(
+            function(TypeSpec::Int { signed: true }, None),
+            Err((CodegenError::IncompatibleTypes(_, Type::Void), _))
+        ));
+    }
+}
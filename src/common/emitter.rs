@@ -1,16 +1,25 @@
-pub struct Emitter(Vec<String>);
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct Emitter {
+    text: Vec<String>,
+    rodata: Vec<String>,
+    data: Vec<String>,
+    bss: Vec<String>,
+    strings: HashMap<Vec<u8>, String>,
+}
 
 impl Emitter {
     pub fn new() -> Self {
-        Emitter(Vec::new())
+        Self::default()
     }
 
     pub fn emit(&mut self, s: &str) {
-        self.0.push(s.to_owned());
+        self.text.push(s.to_owned());
     }
 
     pub fn emit_tabbed(&mut self, n: usize, s: &str) {
-        self.0.push(format!("{}{}", "\t".repeat(n), s));
+        self.text.push(format!("{}{}", "\t".repeat(n), s));
     }
 
     pub fn emit_label(&mut self, s: &str) {
@@ -25,9 +34,89 @@ impl Emitter {
         self.emit_tabbed(1, s)
     }
 
-    pub fn collect(self) -> String {
-        self.0.join("\n") + "\n"
+    /// Index of the next line, for inserting code there once it is known
+    pub fn position(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn insert_instr(&mut self, index: usize, s: &str) {
+        self.text.insert(index, format!("\t{}", s))
+    }
+
+    /// Places a string literal in `.rodata` under the label made by `label`, unless an identical
+    /// one was already placed, returning the label to reference it by
+    pub fn emit_string(&mut self, bytes: Vec<u8>, label: impl FnOnce() -> String) -> String {
+        if let Some(label) = self.strings.get(&bytes) {
+            return label.clone();
+        }
+
+        let label = label();
+        self.rodata.push(format!("{}:", label));
+        self.rodata
+            .push(format!("\t.string \"{}\"", escape_string(&bytes)));
+        self.strings.insert(bytes, label.clone());
+        label
+    }
+
+    /// Defines `label` in `.data`, its contents given by `values` in order. Only `global`
+    /// labels are visible to other translation units.
+    pub fn emit_data(&mut self, label: &str, align: isize, values: &[String], global: bool) {
+        if global {
+            self.data.push(format!("\t.globl {}", label));
+        }
+        self.data.push(format!("\t.balign {}", align));
+        self.data.push(format!("{}:", label));
+        self.data
+            .extend(values.iter().map(|value| format!("\t{}", value)));
     }
+
+    /// Defines `label` in `.bss`, which is zeroed when the program starts
+    pub fn emit_bss(&mut self, label: &str, size: isize, align: isize, global: bool) {
+        if global {
+            self.bss.push(format!("\t.globl {}", label));
+        }
+        self.bss.push(format!("\t.balign {}", align));
+        self.bss.push(format!("{}:", label));
+        self.bss.push(format!("\t.zero {}", size));
+    }
+
+    /// Places a table of 32-bit offsets from `label` to each of `targets` in `.rodata`
+    pub fn emit_jump_table(&mut self, label: &str, targets: &[String]) {
+        self.rodata.push("\t.balign 4".to_owned());
+        self.rodata.push(format!("{}:", label));
+        self.rodata.extend(
+            targets
+                .iter()
+                .map(|target| format!("\t.long {} - {}", target, label)),
+        );
+    }
+
+    pub fn collect(mut self) -> String {
+        for (section, mut lines) in [
+            (".section .rodata", std::mem::take(&mut self.rodata)),
+            (".data", std::mem::take(&mut self.data)),
+            (".bss", std::mem::take(&mut self.bss)),
+        ] {
+            if !lines.is_empty() {
+                self.emit_directive(section);
+                self.text.append(&mut lines);
+            }
+        }
+        self.text.join("\n") + "\n"
+    }
+}
+
+/// Escapes bytes for a `.string` directive, which appends the terminating null itself
+fn escape_string(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            b'"' => "\\\"".to_owned(),
+            b'\\' => "\\\\".to_owned(),
+            0x20..=0x7e => (b as char).to_string(),
+            _ => format!("\\{:03o}", b),
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -37,42 +126,52 @@ mod tests {
     #[test]
     fn new() {
         let emitter = Emitter::new();
-        assert_eq!(emitter.0, Vec::<String>::new());
+        assert_eq!(emitter.text, Vec::<String>::new());
     }
 
     #[test]
     fn emit() {
         let mut emitter = Emitter::new();
         emitter.emit("test");
-        assert_eq!(emitter.0, vec!["test"]);
+        assert_eq!(emitter.text, vec!["test"]);
     }
 
     #[test]
     fn emit_tabbed() {
         let mut emitter = Emitter::new();
         emitter.emit_tabbed(2, "test");
-        assert_eq!(emitter.0, vec!["\t\ttest"]);
+        assert_eq!(emitter.text, vec!["\t\ttest"]);
     }
 
     #[test]
     fn emit_label() {
         let mut emitter = Emitter::new();
         emitter.emit_label("test");
-        assert_eq!(emitter.0, vec!["test:"]);
+        assert_eq!(emitter.text, vec!["test:"]);
     }
 
     #[test]
     fn emit_instr() {
         let mut emitter = Emitter::new();
         emitter.emit_instr("test");
-        assert_eq!(emitter.0, vec!["\ttest"]);
+        assert_eq!(emitter.text, vec!["\ttest"]);
     }
 
     #[test]
     fn emit_directive() {
         let mut emitter = Emitter::new();
         emitter.emit_directive("test");
-        assert_eq!(emitter.0, vec!["\ttest"]);
+        assert_eq!(emitter.text, vec!["\ttest"]);
+    }
+
+    #[test]
+    fn insert_instr() {
+        let mut emitter = Emitter::new();
+        emitter.emit_label("test");
+        let position = emitter.position();
+        emitter.emit_instr("test2");
+        emitter.insert_instr(position, "test1");
+        assert_eq!(emitter.text, vec!["test:", "\ttest1", "\ttest2"]);
     }
 
     #[test]
@@ -82,4 +181,42 @@ mod tests {
         emitter.emit("test2");
         assert_eq!(emitter.collect(), "test1\ntest2\n");
     }
+
+    #[test]
+    fn emit_string() {
+        let mut emitter = Emitter::new();
+        let label = emitter.emit_string(b"a\"b\n".to_vec(), || ".Lstr_0".to_owned());
+        assert_eq!(label, ".Lstr_0");
+        let label = emitter.emit_string(b"a\"b\n".to_vec(), || ".Lstr_1".to_owned());
+        assert_eq!(label, ".Lstr_0");
+        emitter.emit("test");
+        assert_eq!(
+            emitter.collect(),
+            "test\n\t.section .rodata\n.Lstr_0:\n\t.string \"a\\\"b\\012\"\n"
+        );
+    }
+
+    #[test]
+    fn emit_globals() {
+        let mut emitter = Emitter::new();
+        emitter.emit_bss("a", 4, 4, true);
+        emitter.emit_data("b", 8, &[".quad 1".to_owned()], true);
+        emitter.emit_bss("c", 1, 1, false);
+        assert_eq!(
+            emitter.collect(),
+            "\t.data\n\t.globl b\n\t.balign 8\nb:\n\t.quad 1\n\
+             \t.bss\n\t.globl a\n\t.balign 4\na:\n\t.zero 4\n\
+             \t.balign 1\nc:\n\t.zero 1\n"
+        );
+    }
+
+    #[test]
+    fn emit_jump_table() {
+        let mut emitter = Emitter::new();
+        emitter.emit_jump_table("t", &["a".to_owned(), "b".to_owned()]);
+        assert_eq!(
+            emitter.collect(),
+            "\t.section .rodata\n\t.balign 4\nt:\n\t.long a - t\n\t.long b - t\n"
+        );
+    }
 }
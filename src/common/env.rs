@@ -1,63 +1,424 @@
-use super::Span;
-use std::collections::HashMap;
+use super::{
+    ty::{align_to, StructRef, TagKind},
+    CodegenError, Span, Spanned, Type,
+};
+use std::{collections::HashMap, fmt, iter};
 
-const WORD_IN_BYTES: isize = 8;
+pub const WORD_IN_BYTES: isize = 8;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub location: Location,
+    pub ty: Type,
+    pub span: Span,
+}
+
+/// Where the storage of a variable is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// A slot at a fixed offset from `%rbp`
+    Local(isize),
+    /// Static storage under a label, addressed relative to `%rip`
+    Global(String),
+}
+
+/// Formats as an operand addressing the storage
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Local(offset) => write!(f, "{}(%rbp)", offset),
+            Location::Global(label) => write!(f, "{}(%rip)", label),
+        }
+    }
+}
+
+/// Anything named in the ordinary identifier namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Symbol {
+    Variable(Variable),
+    /// An enumeration constant and its value
+    Enumerator(i64, Span),
+    /// A name declared with `typedef` for a type
+    Typedef(Type, Span),
+}
+
+impl Symbol {
+    /// Where the symbol was declared
+    pub fn span(&self) -> Span {
+        match self {
+            Symbol::Variable(var) => var.span,
+            Symbol::Enumerator(_, span) => *span,
+            Symbol::Typedef(_, span) => *span,
+        }
+    }
+}
+
+/// What is known of a variable declared at file scope, across all its declarations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Global {
+    /// Visible to other translation units, decided by the first declaration
+    pub external: bool,
+    /// Storage was emitted for an initializer
+    pub defined: bool,
+    /// Declared without `extern` or an initializer, which defines it as zero unless it is
+    /// defined elsewhere in the translation unit
+    pub tentative: bool,
+}
+
+/// What is known of a function from its declarations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub ret: Type,
+    /// The type of each parameter, and where it was declared
+    pub params: Vec<Spanned<Type>>,
+    /// Takes variable arguments after its parameters. Without parameters, it was declared
+    /// with `()` and takes any arguments.
+    pub variadic: bool,
+    /// Where the function was first declared
+    pub span: Span,
+    /// Where the function was defined, if it has been
+    pub definition: Option<Span>,
+    /// Visible to other translation units, decided by the first declaration
+    pub external: bool,
+}
+
+impl Function {
+    /// The type of an expression designating this function
+    pub fn ty(&self) -> Type {
+        Type::Function(
+            Box::new(self.ret.clone()),
+            self.params.iter().map(|(ty, _)| ty.clone()).collect(),
+            self.variadic,
+        )
+    }
+
+    /// Whether its parameters were declared, rather than left unspecified by `()`
+    pub fn is_prototyped(&self) -> bool {
+        !self.params.is_empty() || !self.variadic
+    }
+
+    /// Whether `other` takes the same parameter types. Unspecified parameters agree with
+    /// any.
+    pub fn has_params(&self, other: &Function) -> bool {
+        if !self.is_prototyped() || !other.is_prototyped() {
+            return true;
+        }
+        self.variadic == other.variadic
+            && self.params.len() == other.params.len()
+            && self
+                .params
+                .iter()
+                .zip(&other.params)
+                .all(|((a, _), (b, _))| a == b)
+    }
+}
+
+/// Where a variadic function keeps what `va_start` needs to find its variable arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegSaveArea<'src> {
+    /// Offset from `%rbp` of the argument registers saved by the prologue, the six integer
+    /// registers followed by the eight vector registers 16 bytes apart
+    pub offset: isize,
+    /// Offset into the area of the first integer register not taken by a parameter
+    pub gp_offset: isize,
+    /// Offset into the area of the first vector register not taken by a parameter
+    pub fp_offset: isize,
+    /// Offset from `%rbp` of the first variable argument passed on the stack
+    pub overflow: isize,
+    /// Name of the last parameter, which `va_start` is given, if it has one
+    pub last_param: Option<&'src str>,
+}
+
+/// Tag of the struct a `va_list` is an array of one of. Its leading underscores keep it
+/// from clashing with tags in programs. `va_list` itself is an ordinary typedef name in file
+/// scope, which programs may shadow.
+const VA_LIST_TAG: &str = "__va_list_tag";
+
+/// What a struct, union or enum tag names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tag {
+    Struct(StructRef),
+    /// An enum, and whether its enumerators have been listed yet
+    Enum(bool),
+}
+
+impl Tag {
+    pub fn kind(&self) -> TagKind {
+        match self {
+            Tag::Struct(s) => s.kind(),
+            Tag::Enum(_) => TagKind::Enum,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Tag::Struct(s) => s.is_complete(),
+            Tag::Enum(defined) => *defined,
+        }
+    }
+}
+
+/// Tags live in their own namespace, scoped like variables
+#[derive(Debug, Clone, Default)]
+struct Scope<'src> {
+    vars: HashMap<&'src str, Symbol>,
+    tags: HashMap<&'src str, (Tag, Span)>,
+    sp: isize,
+}
+
+impl Scope<'_> {
+    fn get(&self, key: &str) -> Option<Variable> {
+        match self.vars.get(key)? {
+            Symbol::Variable(var) => Some(var.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Locals live in fixed slots below `%rbp`. `sp` is the offset of the lowest slot in use,
+/// and `frame_size` the most the current function has needed at once. File scope sits
+/// under every block scope.
+#[derive(Debug, Clone, Default)]
 pub struct Environment<'src> {
     pub sp: isize,
-    envs: Vec<HashMap<&'src str, (isize, Span)>>,
+    pub frame_size: isize,
+    /// Return type of the current function
+    pub ret: Option<Type>,
+    /// Slot holding the address a struct returned in memory is written to
+    pub ret_ptr: Option<isize>,
+    /// Saved argument registers of the current function, if it is variadic
+    pub reg_save_area: Option<RegSaveArea<'src>>,
+    /// Name of the current function, which its labels are named after
+    pub function: Option<&'src str>,
+    /// Problems that do not stop compilation, in the order they were found
+    pub warnings: Vec<Spanned<CodegenError<'src>>>,
+    /// Labels of the current function, which are visible throughout it
+    labels: HashMap<&'src str, Span>,
+    /// Labels named by a `goto` in the current function
+    gotos: Vec<Spanned<&'src str>>,
+    envs: Vec<Scope<'src>>,
+    globals: Scope<'src>,
+    linkage: HashMap<&'src str, Global>,
+    functions: HashMap<&'src str, Function>,
 }
 
 impl<'src> Environment<'src> {
     pub fn new() -> Self {
+        let tag = va_list_tag();
+        let va_list = Type::Struct(tag.clone()).array_of(1);
         Self {
             sp: 0,
+            frame_size: 0,
+            ret: None,
+            ret_ptr: None,
+            reg_save_area: None,
+            function: None,
+            warnings: vec![],
+            labels: HashMap::new(),
+            gotos: vec![],
             envs: vec![],
+            globals: Scope {
+                vars: HashMap::from([("va_list", Symbol::Typedef(va_list, Span::new(0, 0)))]),
+                tags: HashMap::from([(VA_LIST_TAG, (Tag::Struct(tag), Span::new(0, 0)))]),
+                ..Default::default()
+            },
+            linkage: HashMap::new(),
+            functions: HashMap::new(),
         }
     }
 
-    pub fn put(&mut self, key: &'src str, span: Span) -> bool {
-        self.decrement_sp();
-        (!self.contains(key))
-            .then_some(
-                self.envs
-                    .iter_mut()
-                    .last()
-                    .map(|env| env.insert(key, (self.sp, span)))
-                    .flatten(),
-            )
-            .flatten()
-            .is_none()
+    pub fn put(&mut self, key: &'src str, ty: Type, span: Span) -> bool {
+        let location = Location::Local(self.allocate(&ty));
+        self.insert(key, Symbol::Variable(Variable { location, ty, span }))
+    }
+
+    /// Binds `key` to static storage under `label`
+    pub fn put_global(&mut self, key: &'src str, label: String, ty: Type, span: Span) -> bool {
+        let location = Location::Global(label);
+        self.insert(key, Symbol::Variable(Variable { location, ty, span }))
+    }
+
+    /// The state of the file scope variable `key`, which is external unless `external` says
+    /// otherwise on its first declaration
+    pub fn linkage_mut(&mut self, key: &'src str, external: bool) -> &mut Global {
+        self.linkage.entry(key).or_insert(Global {
+            external,
+            defined: false,
+            tentative: false,
+        })
     }
 
-    pub fn get(&self, key: &str) -> Option<(isize, Span)> {
-        self.envs.iter().rev().find_map(|env| env.get(key).copied())
+    /// File scope variables only tentatively defined, in the order they were declared
+    pub fn tentative_globals(&self) -> Vec<(&'src str, Variable, bool)> {
+        let mut tentative = self
+            .linkage
+            .iter()
+            .filter(|(_, global)| global.tentative && !global.defined)
+            .filter_map(|(&key, global)| Some((key, self.globals.get(key)?, global.external)))
+            .collect::<Vec<_>>();
+        tentative.sort_by_key(|(_, var, _)| var.span.start);
+        tentative
+    }
+
+    pub fn put_enumerator(&mut self, key: &'src str, value: i64, span: Span) -> bool {
+        self.insert(key, Symbol::Enumerator(value, span))
+    }
+
+    pub fn put_typedef(&mut self, key: &'src str, ty: Type, span: Span) -> bool {
+        self.insert(key, Symbol::Typedef(ty, span))
+    }
+
+    /// Allocates an unnamed slot for a value of type `ty` that lives until the end of the
+    /// current scope, returning its offset from `%rbp`
+    pub fn put_temp(&mut self, ty: &Type) -> isize {
+        self.allocate(ty)
+    }
+
+    /// Binds `key` to a caller-allocated slot at a fixed offset from `%rbp`, used for
+    /// arguments passed on the stack. Does not move the stack pointer.
+    pub fn put_at(&mut self, key: &'src str, offset: isize, ty: Type, span: Span) -> bool {
+        let location = Location::Local(offset);
+        self.insert(key, Symbol::Variable(Variable { location, ty, span }))
+    }
+
+    pub fn get_symbol(&self, key: &str) -> Option<Symbol> {
+        self.scopes().find_map(|env| env.vars.get(key).cloned())
+    }
+
+    /// Looks `key` up as a variable, which may be hidden by an enumerator in an inner scope
+    pub fn get(&self, key: &str) -> Option<Variable> {
+        match self.get_symbol(key)? {
+            Symbol::Variable(var) => Some(var),
+            _ => None,
+        }
+    }
+
+    /// Looks `key` up in the innermost scope only, where a redeclaration would clash.
+    pub fn get_in_scope(&self, key: &str) -> Option<Symbol> {
+        self.scopes()
+            .next()
+            .and_then(|env| env.vars.get(key).cloned())
     }
 
     pub fn contains(&self, key: &str) -> bool {
-        self.get(key).is_some()
+        self.get_symbol(key).is_some()
+    }
+
+    /// The type of `va_list`, the same wherever it is written
+    pub fn va_list(&self) -> Type {
+        match self.globals.tags.get(VA_LIST_TAG) {
+            Some((Tag::Struct(s), _)) => Type::Struct(s.clone()).array_of(1),
+            _ => unreachable!("declared by Environment::new"),
+        }
+    }
+
+    /// Declares or replaces `tag` in the current scope
+    pub fn put_tag(&mut self, key: &'src str, tag: Tag, span: Span) {
+        self.scope_mut().tags.insert(key, (tag, span));
+    }
+
+    pub fn get_tag(&self, key: &str) -> Option<(Tag, Span)> {
+        self.scopes().find_map(|env| env.tags.get(key).cloned())
+    }
+
+    pub fn get_tag_in_scope(&self, key: &str) -> Option<(Tag, Span)> {
+        self.scopes()
+            .next()
+            .and_then(|env| env.tags.get(key).cloned())
+    }
+
+    /// Records the signature of a function so calls to it are checked and typed correctly
+    pub fn put_function(&mut self, key: &'src str, function: Function) {
+        self.functions.insert(key, function);
+    }
+
+    pub fn get_function(&self, key: &str) -> Option<Function> {
+        self.functions.get(key).cloned()
+    }
+
+    /// Defines the label `key` in the current function, unless it already is
+    pub fn put_label(&mut self, key: &'src str, span: Span) -> bool {
+        if self.labels.contains_key(key) {
+            return false;
+        }
+        self.labels.insert(key, span);
+        true
+    }
+
+    pub fn get_label(&self, key: &str) -> Option<Span> {
+        self.labels.get(key).copied()
+    }
+
+    /// The symbol the label `key` of the current function is emitted as. It is local to the
+    /// object file and, containing a `.`, cannot clash with an identifier or a label made by a
+    /// `LabelTracker`.
+    pub fn label_symbol(&self, key: &str) -> String {
+        let function = self.function.expect("label outside of a function");
+        format!(".L{}.{}", function, key)
+    }
+
+    /// Records a `goto` to `key`, which may be defined later in the function
+    pub fn use_label(&mut self, key: &'src str, span: Span) {
+        self.gotos.push((key, span));
+    }
+
+    /// Leaves the current function, returning its labels and every `goto` to them
+    pub fn take_labels(&mut self) -> (HashMap<&'src str, Span>, Vec<Spanned<&'src str>>) {
+        (
+            std::mem::take(&mut self.labels),
+            std::mem::take(&mut self.gotos),
+        )
     }
 
     pub fn new_scope(&mut self) {
-        self.envs.push(HashMap::new())
+        self.envs.push(Scope {
+            sp: self.sp,
+            ..Default::default()
+        })
+    }
+
+    /// Ends the innermost scope, releasing its slots for reuse by later scopes
+    pub fn end_scope(&mut self) {
+        if let Some(env) = self.envs.pop() {
+            self.sp = env.sp;
+        }
     }
 
-    pub fn end_scope(&mut self) -> Option<isize> {
-        (!self.envs.is_empty())
-            .then_some(
-                self.envs
-                    .pop()
-                    .map(|env| env.len() as isize * WORD_IN_BYTES),
-            )
-            .flatten()
+    fn allocate(&mut self, ty: &Type) -> isize {
+        self.sp -= slot_size(ty);
+        self.frame_size = self.frame_size.max(-self.sp);
+        self.sp
     }
 
-    fn decrement_sp(&mut self) {
-        self.sp -= WORD_IN_BYTES;
+    fn insert(&mut self, key: &'src str, symbol: Symbol) -> bool {
+        self.get_in_scope(key).is_none() && self.scope_mut().vars.insert(key, symbol).is_none()
     }
 
-    fn _increment_sp(&mut self) {
-        self.sp += WORD_IN_BYTES;
+    /// Innermost first, ending with file scope
+    fn scopes(&self) -> impl Iterator<Item = &Scope<'src>> {
+        self.envs.iter().rev().chain(iter::once(&self.globals))
     }
+
+    fn scope_mut(&mut self) -> &mut Scope<'src> {
+        self.envs.last_mut().unwrap_or(&mut self.globals)
+    }
+}
+
+/// The state of a traversal of variable arguments, laid out as the System V ABI specifies
+fn va_list_tag() -> StructRef {
+    let unsigned = Type::Int { signed: false };
+    let pointer = Type::Char { signed: true }.pointer_to();
+    let s = StructRef::new(TagKind::Struct, Some(VA_LIST_TAG.to_owned()));
+    s.define(vec![
+        ("gp_offset".to_owned(), unsigned.clone()),
+        ("fp_offset".to_owned(), unsigned),
+        ("overflow_arg_area".to_owned(), pointer.clone()),
+        ("reg_save_area".to_owned(), pointer),
+    ]);
+    s
+}
+
+/// Number of bytes a local of type `ty` occupies on the stack, rounded up to whole words
+pub fn slot_size(ty: &Type) -> isize {
+    align_to(ty.size(), WORD_IN_BYTES)
 }
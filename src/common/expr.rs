@@ -1,16 +1,42 @@
 use super::{
+    abi::{
+        assign_registers, classify, emit_load_eightbyte, emit_store_eightbyte, ArgClass,
+        ARG_REGISTERS, RET_REGISTERS, SSE_ARG_REGISTERS, SSE_RET_REGISTERS,
+    },
+    codegen::{
+        emit_cast, emit_cmp_zero, emit_convert, emit_copy, emit_from_xmm0, emit_load_scalar,
+        emit_store_scalar, sse_suffix,
+    },
     emitter::Emitter,
+    env::{slot_size, Function, Symbol, WORD_IN_BYTES},
     label_tracker::{LabelKind, LabelTracker},
-    BinaryOperator, Codegen, CodegenError, Desugar, Environment, Span, Spanned, UnaryOperator,
+    ty::{check_operand, check_operands, condition_ty, member_of, object_ty},
+    BinaryOperator, Codegen, CodegenError, ConstEval, Desugar, Environment, Span, Spanned, Type,
+    TypeOf, TypeSpec, UnaryOperator,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr<'src> {
-    LiteralInteger(u64),
+    /// The value is kept extended to 64 bits from its type, as it would be in `%rax`
+    LiteralInteger(u64, Type),
+    LiteralFloat(f64, Type),
+    LiteralString(Vec<u8>),
     Variable(&'src str),
     Unary(UnaryOperator, Box<Spanned<Self>>),
     Binary(Box<Spanned<Self>>, BinaryOperator, Box<Spanned<Self>>),
     Ternary(Box<Spanned<Self>>, Box<Spanned<Self>>, Box<Spanned<Self>>),
+    Call(Box<Spanned<Self>>, Vec<Spanned<Self>>),
+    Index(Box<Spanned<Self>>, Box<Spanned<Self>>),
+    SizeOf(Box<Spanned<Self>>),
+    SizeOfType(TypeSpec<'src>),
+    AlignOf(TypeSpec<'src>),
+    Cast(TypeSpec<'src>, Box<Spanned<Self>>),
+    /// `va_start`, `va_arg` and `va_end`, each given the `va_list`. `va_start` is also
+    /// given the last parameter, which the variable arguments follow.
+    VaStart(Box<Spanned<Self>>, Box<Spanned<Self>>),
+    VaArg(Box<Spanned<Self>>, TypeSpec<'src>),
+    VaEnd(Box<Spanned<Self>>),
+    Member(Box<Spanned<Self>>, Spanned<&'src str>),
 }
 
 impl<'src> Codegen<'src> for Vec<Spanned<Expr<'src>>> {
@@ -34,155 +60,383 @@ impl<'src> Codegen<'src> for Spanned<Expr<'src>> {
         em: &mut Emitter,
         env: &mut Environment<'src>,
     ) -> Result<(), Spanned<CodegenError<'src>>> {
+        match &self {
+            (Expr::Unary(op, rhs), span) => check_operand(*op, rhs, *span, env)?,
+            (Expr::Binary(lhs, op, rhs), span) => check_operands(*op, lhs, rhs, *span, env)?,
+            _ => {}
+        }
+
         match self {
-            (Expr::LiteralInteger(i), _) => {
-                em.emit_instr(&format!("mov ${}, %rax", i));
+            (Expr::LiteralInteger(i, _), _) => emit_immediate(em, i),
+
+            (Expr::LiteralFloat(f, ty), _) => match ty {
+                Type::Float => emit_immediate(em, (f as f32).to_bits() as u64),
+                _ => emit_immediate(em, f.to_bits()),
+            },
+
+            // Like any other array, a string literal evaluates to its address
+            (Expr::LiteralString(bytes), _) => {
+                let label = em.emit_string(bytes, || lt.create(LabelKind::String));
+                em.emit_instr(&format!("lea {}(%rip), %rax", label));
             }
 
             (Expr::Variable(name), span) => {
-                let (offset, _) = env
-                    .get(name)
-                    .ok_or((CodegenError::UndeclaredVariable(name), span))?;
+                let var = match env.get_symbol(name) {
+                    Some(Symbol::Variable(var)) => var,
+                    Some(Symbol::Enumerator(value, _)) => {
+                        em.emit_instr(&format!("mov ${}, %rax", value));
+                        return Ok(());
+                    }
+                    Some(Symbol::Typedef(_, _)) => {
+                        return Err((CodegenError::UndeclaredVariable(name), span))
+                    }
+                    // A function evaluates to its address
+                    None => match env.get_function(name) {
+                        Some(_) => {
+                            em.emit_instr(&format!("lea {}(%rip), %rax", name));
+                            return Ok(());
+                        }
+                        None => return Err((CodegenError::UndeclaredVariable(name), span)),
+                    },
+                };
 
-                em.emit_instr(&format!("mov {}(%rbp), %rax", offset));
+                // Aggregates are not loaded, arrays decay to the address of their first
+                // element and structs are handled through their address
+                if var.ty.is_aggregate() {
+                    em.emit_instr(&format!("lea {}, %rax", var.location));
+                } else {
+                    emit_load_scalar(em, &var.ty, &var.location.to_string());
+                }
             }
 
             /* Unary */
             (Expr::Unary(UnaryOperator::Plus, rhs), _) => rhs.code_gen(lt, em, env)?,
 
+            // Negating a floating point value flips its sign bit
             (Expr::Unary(UnaryOperator::Minus, rhs), _) => {
+                let ty = rhs.ty(env)?.promote();
                 rhs.code_gen(lt, em, env)?;
-                em.emit_instr("neg %rax");
+                match ty {
+                    Type::Float => em.emit_instr("btc $31, %rax"),
+                    Type::Double => em.emit_instr("btc $63, %rax"),
+                    _ => {
+                        em.emit_instr("neg %rax");
+                        emit_convert(em, &ty);
+                    }
+                }
             }
 
             (Expr::Unary(UnaryOperator::LogicalNot, rhs), _) => {
+                let ty = rhs.ty(env)?;
                 rhs.code_gen(lt, em, env)?;
-                em.emit_instr("cmpl $0, %rax");
+                emit_cmp_zero(em, &ty);
                 em.emit_instr("mov $0, %rax");
                 em.emit_instr("sete %al");
             }
 
             (Expr::Unary(UnaryOperator::BitwiseNot, rhs), _) => {
+                let ty = rhs.ty(env)?.promote();
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("not %rax");
+                emit_convert(em, &ty);
+            }
+
+            (Expr::Unary(UnaryOperator::Deref, rhs), span) => {
+                let ty = rhs.ty(env)?.decay();
+                let pointee = ty
+                    .pointee()
+                    .filter(|pointee| !pointee.is_void())
+                    .ok_or_else(|| (CodegenError::InvalidDereference(ty.clone()), span))?;
+
+                let pointee = pointee.clone();
+                rhs.code_gen(lt, em, env)?;
+                emit_load(em, &pointee);
+            }
+
+            (Expr::Member(lhs, name), _) => {
+                let member = member_of(&lhs, name, env)?;
+
+                lhs.code_gen(lt, em, env)?;
+                if member.offset != 0 {
+                    em.emit_instr(&format!("add ${}, %rax", member.offset));
+                }
+                emit_load(em, &member.ty);
+            }
+
+            (Expr::Unary(UnaryOperator::AddressOf, rhs), _) => {
+                let lvalue = rhs
+                    .0
+                    .as_lvalue(env)
+                    .ok_or((CodegenError::InvalidAddressOf, rhs.1))?;
+
+                (lvalue, rhs.1).code_gen(lt, em, env)?;
+            }
+
+            // The address of the operand is kept in %rcx, so it is only evaluated once.
+            // Pointers step by the size of what they point to.
+            (
+                Expr::Unary(
+                    op @ (UnaryOperator::PreIncrement
+                    | UnaryOperator::PreDecrement
+                    | UnaryOperator::PostIncrement
+                    | UnaryOperator::PostDecrement),
+                    rhs,
+                ),
+                _,
+            ) => {
+                let ty = rhs.ty(env)?;
+                let lvalue = rhs
+                    .0
+                    .as_lvalue(env)
+                    .filter(|_| !ty.is_array())
+                    .ok_or((CodegenError::InvalidAssignmentTarget, rhs.1))?;
+                if !ty.is_arithmetic() && !ty.is_pointer() {
+                    return Err((CodegenError::InvalidIncrement(ty), rhs.1));
+                }
+                check_writable(&rhs, env)?;
+                let instr = match op {
+                    UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => "add",
+                    _ => "sub",
+                };
+                let postfix = matches!(
+                    op,
+                    UnaryOperator::PostIncrement | UnaryOperator::PostDecrement
+                );
+
+                (lvalue, rhs.1).code_gen(lt, em, env)?;
+                em.emit_instr("mov %rax, %rcx");
+                emit_load_scalar(em, &ty, "(%rcx)");
+                if postfix {
+                    em.emit_instr("push %rax");
+                }
+                if ty.is_float() {
+                    em.emit_instr("movq %rax, %xmm0");
+                    em.emit_instr("mov $1, %eax");
+                    em.emit_instr(&format!("cvtsi2{}q %rax, %xmm1", sse_suffix(&ty)));
+                    emit_sse_op(em, instr, &ty);
+                } else {
+                    let step = ty.pointee().map_or(1, Type::size);
+                    em.emit_instr(&format!("{} ${}, %rax", instr, step));
+                    emit_convert(em, &ty);
+                }
+                emit_store_scalar(em, &ty, "(%rcx)");
+                if postfix {
+                    em.emit_instr("pop %rax");
+                }
             }
 
             /* Binary */
-            // Math Ops
+            // Floating point operands are converted to their common type, then operated on
+            // in xmm registers. Comparisons with NaN are false, other than `!=`.
+            (
+                Expr::Binary(
+                    lhs,
+                    op @ (BinaryOperator::Plus
+                    | BinaryOperator::Minus
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::EqEq
+                    | BinaryOperator::Ne
+                    | BinaryOperator::Ge
+                    | BinaryOperator::Gt
+                    | BinaryOperator::Le
+                    | BinaryOperator::Lt),
+                    rhs,
+                ),
+                _,
+            ) if float_operands(&lhs, &rhs, env) => {
+                let (lhs_ty, rhs_ty) = (lhs.ty(env)?, rhs.ty(env)?);
+                let ty = lhs_ty.common(&rhs_ty);
+                let sfx = sse_suffix(&ty);
+
+                lhs.code_gen(lt, em, env)?;
+                emit_cast(em, lt, &lhs_ty, &ty);
+                em.emit_instr("push %rax");
+                rhs.code_gen(lt, em, env)?;
+                emit_cast(em, lt, &rhs_ty, &ty);
+                em.emit_instr("pop %rcx");
+                em.emit_instr("movq %rcx, %xmm0");
+                em.emit_instr("movq %rax, %xmm1");
+
+                let compare = |em: &mut Emitter, operands: &str| {
+                    em.emit_instr(&format!("ucomi{} {}", sfx, operands));
+                    em.emit_instr("mov $0, %rax");
+                };
+                match op {
+                    BinaryOperator::Plus => emit_sse_op(em, "add", &ty),
+                    BinaryOperator::Minus => emit_sse_op(em, "sub", &ty),
+                    BinaryOperator::Multiply => emit_sse_op(em, "mul", &ty),
+                    BinaryOperator::Divide => emit_sse_op(em, "div", &ty),
+                    // Unordered operands set the zero, parity and carry flags, so `<` is
+                    // checked as a swapped `>` and equality also checks the parity flag
+                    BinaryOperator::Gt => {
+                        compare(em, "%xmm1, %xmm0");
+                        em.emit_instr("seta %al");
+                    }
+                    BinaryOperator::Ge => {
+                        compare(em, "%xmm1, %xmm0");
+                        em.emit_instr("setae %al");
+                    }
+                    BinaryOperator::Lt => {
+                        compare(em, "%xmm0, %xmm1");
+                        em.emit_instr("seta %al");
+                    }
+                    BinaryOperator::Le => {
+                        compare(em, "%xmm0, %xmm1");
+                        em.emit_instr("setae %al");
+                    }
+                    BinaryOperator::EqEq => {
+                        compare(em, "%xmm1, %xmm0");
+                        em.emit_instr("sete %al");
+                        em.emit_instr("setnp %cl");
+                        em.emit_instr("and %cl, %al");
+                    }
+                    _ => {
+                        compare(em, "%xmm1, %xmm0");
+                        em.emit_instr("setne %al");
+                        em.emit_instr("setp %cl");
+                        em.emit_instr("or %cl, %al");
+                    }
+                }
+            }
+
+            // Math Ops. Operating on the full registers then converting to the common type
+            // gives the same result as operating at its width.
             (Expr::Binary(lhs, BinaryOperator::Plus, rhs), _) => {
+                let (lhs_ty, rhs_ty) = (lhs.ty(env)?.decay(), rhs.ty(env)?.decay());
+
                 lhs.code_gen(lt, em, env)?;
                 em.emit_instr("push %rax");
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("pop %rcx");
+                if let Some(pointee) = lhs_ty.pointee() {
+                    emit_scale(em, "%rax", pointee);
+                } else if let Some(pointee) = rhs_ty.pointee() {
+                    emit_scale(em, "%rcx", pointee);
+                }
                 em.emit_instr("add %rcx, %rax");
+                if !lhs_ty.is_pointer() && !rhs_ty.is_pointer() {
+                    emit_convert(em, &lhs_ty.common(&rhs_ty));
+                }
             }
 
             (Expr::Binary(lhs, BinaryOperator::Multiply, rhs), _) => {
+                let ty = lhs.ty(env)?.common(&rhs.ty(env)?);
+
                 lhs.code_gen(lt, em, env)?;
                 em.emit_instr("push %rax");
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("pop %rcx");
                 em.emit_instr("imul %rcx, %rax");
+                emit_convert(em, &ty);
             }
 
             (Expr::Binary(lhs, BinaryOperator::Minus, rhs), _) => {
+                let (lhs_ty, rhs_ty) = (lhs.ty(env)?.decay(), rhs.ty(env)?.decay());
+
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("push %rax");
                 lhs.code_gen(lt, em, env)?;
                 em.emit_instr("pop %rcx");
-                em.emit_instr("sub %rax, %rcx");
+                match (lhs_ty.pointee(), rhs_ty.is_pointer()) {
+                    // Difference of two pointers is measured in elements
+                    (Some(pointee), true) => {
+                        em.emit_instr("sub %rcx, %rax");
+                        if pointee.size() != 1 {
+                            em.emit_instr("cqo");
+                            em.emit_instr(&format!("mov ${}, %rcx", pointee.size()));
+                            em.emit_instr("idiv %rcx");
+                        }
+                    }
+                    (Some(pointee), false) => {
+                        emit_scale(em, "%rcx", pointee);
+                        em.emit_instr("sub %rcx, %rax");
+                    }
+                    (None, _) => {
+                        em.emit_instr("sub %rcx, %rax");
+                        emit_convert(em, &lhs_ty.common(&rhs_ty));
+                    }
+                }
             }
 
+            // Division depends on the width of its operands, so they are first converted
             (Expr::Binary(lhs, BinaryOperator::Divide, rhs), _) => {
-                rhs.code_gen(lt, em, env)?;
-                em.emit_instr("push %rax");
-                lhs.code_gen(lt, em, env)?;
-                em.emit_instr("pop %rcx");
-                em.emit_instr("cqo");
-                em.emit_instr("idiv %rcx");
-            }
+                let ty = lhs.ty(env)?.common(&rhs.ty(env)?);
 
-            (Expr::Binary(lhs, BinaryOperator::Mod, rhs), _) => {
                 rhs.code_gen(lt, em, env)?;
+                emit_convert(em, &ty);
                 em.emit_instr("push %rax");
                 lhs.code_gen(lt, em, env)?;
+                emit_convert(em, &ty);
                 em.emit_instr("pop %rcx");
-                em.emit_instr("cqo");
-                em.emit_instr("idiv %rcx");
-                em.emit_instr("mov %rdx, %rax");
+                emit_divide(em, &ty);
             }
 
-            (Expr::Binary(lhs, BinaryOperator::EqEq, rhs), _) => {
-                lhs.code_gen(lt, em, env)?;
-                em.emit_instr("push %rax");
-                rhs.code_gen(lt, em, env)?;
-                em.emit_instr("pop %rcx");
-                em.emit_instr("cmp %rax, %rcx");
-                em.emit_instr("mov $0, %rax");
-                em.emit_instr("sete %al");
-            }
+            (Expr::Binary(lhs, BinaryOperator::Mod, rhs), _) => {
+                let ty = lhs.ty(env)?.common(&rhs.ty(env)?);
 
-            (Expr::Binary(lhs, BinaryOperator::Ne, rhs), _) => {
-                lhs.code_gen(lt, em, env)?;
-                em.emit_instr("push %rax");
                 rhs.code_gen(lt, em, env)?;
-                em.emit_instr("pop %rcx");
-                em.emit_instr("cmp %rax, %rcx");
-                em.emit_instr("mov $0, %rax");
-                em.emit_instr("setne %al");
-            }
-
-            (Expr::Binary(lhs, BinaryOperator::Ge, rhs), _) => {
-                lhs.code_gen(lt, em, env)?;
+                emit_convert(em, &ty);
                 em.emit_instr("push %rax");
-                rhs.code_gen(lt, em, env)?;
-                em.emit_instr("pop %rcx");
-                em.emit_instr("cmp %rax, %rcx");
-                em.emit_instr("mov $0, %rax");
-                em.emit_instr("setge %al");
-            }
-
-            (Expr::Binary(lhs, BinaryOperator::Gt, rhs), _) => {
                 lhs.code_gen(lt, em, env)?;
-                em.emit_instr("push %rax");
-                rhs.code_gen(lt, em, env)?;
+                emit_convert(em, &ty);
                 em.emit_instr("pop %rcx");
-                em.emit_instr("cmp %rax, %rcx");
-                em.emit_instr("mov $0, %rax");
-                em.emit_instr("setg %al");
+                emit_divide(em, &ty);
+                em.emit_instr("mov %rdx, %rax");
             }
 
-            (Expr::Binary(lhs, BinaryOperator::Le, rhs), _) => {
-                lhs.code_gen(lt, em, env)?;
-                em.emit_instr("push %rax");
-                rhs.code_gen(lt, em, env)?;
-                em.emit_instr("pop %rcx");
-                em.emit_instr("cmp %rax, %rcx");
-                em.emit_instr("mov $0, %rax");
-                em.emit_instr("setle %al");
-            }
+            // Comparisons are made in the common type of both operands, which decides
+            // whether they are signed
+            (
+                Expr::Binary(
+                    lhs,
+                    op @ (BinaryOperator::EqEq
+                    | BinaryOperator::Ne
+                    | BinaryOperator::Ge
+                    | BinaryOperator::Gt
+                    | BinaryOperator::Le
+                    | BinaryOperator::Lt),
+                    rhs,
+                ),
+                _,
+            ) => {
+                let ty = lhs.ty(env)?.common(&rhs.ty(env)?);
+                let set = match (op, ty.is_signed()) {
+                    (BinaryOperator::EqEq, _) => "sete",
+                    (BinaryOperator::Ne, _) => "setne",
+                    (BinaryOperator::Ge, true) => "setge",
+                    (BinaryOperator::Ge, false) => "setae",
+                    (BinaryOperator::Gt, true) => "setg",
+                    (BinaryOperator::Gt, false) => "seta",
+                    (BinaryOperator::Le, true) => "setle",
+                    (BinaryOperator::Le, false) => "setbe",
+                    (BinaryOperator::Lt, true) => "setl",
+                    _ => "setb",
+                };
 
-            (Expr::Binary(lhs, BinaryOperator::Lt, rhs), _) => {
                 lhs.code_gen(lt, em, env)?;
+                emit_convert(em, &ty);
                 em.emit_instr("push %rax");
                 rhs.code_gen(lt, em, env)?;
+                emit_convert(em, &ty);
                 em.emit_instr("pop %rcx");
                 em.emit_instr("cmp %rax, %rcx");
                 em.emit_instr("mov $0, %rax");
-                em.emit_instr("setl %al");
+                em.emit_instr(&format!("{} %al", set));
             }
 
             (Expr::Binary(lhs, BinaryOperator::LogicalAnd, rhs), _) => {
                 let l1 = lt.create(LabelKind::And);
                 let l2 = lt.create(LabelKind::AndShortCircuit);
+                let (lhs_ty, rhs_ty) = (lhs.ty(env)?, rhs.ty(env)?);
 
                 lhs.code_gen(lt, em, env)?;
-                em.emit_instr("cmp $0, %rax");
+                emit_cmp_zero(em, &lhs_ty);
                 em.emit_instr(&format!("jne {}", l1));
                 em.emit_instr(&format!("jmp {}", l2));
                 em.emit_label(&l1);
                 rhs.code_gen(lt, em, env)?;
-                em.emit_instr("cmp $0, %rax");
+                emit_cmp_zero(em, &rhs_ty);
                 em.emit_instr("mov $0, %rax");
                 em.emit_instr("setne %al");
                 em.emit_label(&l2);
@@ -191,111 +445,630 @@ impl<'src> Codegen<'src> for Spanned<Expr<'src>> {
             (Expr::Binary(lhs, BinaryOperator::LogicalOr, rhs), _) => {
                 let l1 = lt.create(LabelKind::Or);
                 let l2 = lt.create(LabelKind::OrShortCircuit);
+                let (lhs_ty, rhs_ty) = (lhs.ty(env)?, rhs.ty(env)?);
 
                 lhs.code_gen(lt, em, env)?;
-                em.emit_instr("cmp $0, %rax");
+                emit_cmp_zero(em, &lhs_ty);
                 em.emit_instr(&format!("je {}", l1));
                 em.emit_instr("mov $1, %rax");
                 em.emit_instr(&format!("jmp {}", l2));
                 em.emit_label(&l1);
                 rhs.code_gen(lt, em, env)?;
-                em.emit_instr("cmp $0, %rax");
+                emit_cmp_zero(em, &rhs_ty);
                 em.emit_instr("mov $0, %rax");
                 em.emit_instr("setne %al");
                 em.emit_label(&l2);
             }
 
             (Expr::Binary(lhs, BinaryOperator::BitwiseAnd, rhs), _) => {
+                let ty = lhs.ty(env)?.common(&rhs.ty(env)?);
+
                 lhs.code_gen(lt, em, env)?;
                 em.emit_instr("push %rax");
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("pop %rcx");
                 em.emit_instr("and %rcx, %rax");
+                emit_convert(em, &ty);
             }
 
             (Expr::Binary(lhs, BinaryOperator::BitwiseOr, rhs), _) => {
+                let ty = lhs.ty(env)?.common(&rhs.ty(env)?);
+
                 lhs.code_gen(lt, em, env)?;
                 em.emit_instr("push %rax");
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("pop %rcx");
                 em.emit_instr("or %rcx, %rax");
+                emit_convert(em, &ty);
             }
 
             (Expr::Binary(lhs, BinaryOperator::BitwiseXor, rhs), _) => {
+                let ty = lhs.ty(env)?.common(&rhs.ty(env)?);
+
                 lhs.code_gen(lt, em, env)?;
                 em.emit_instr("push %rax");
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("pop %rcx");
                 em.emit_instr("xor %rcx, %rax");
+                emit_convert(em, &ty);
             }
 
             (Expr::Binary(lhs, BinaryOperator::LeftShift, rhs), _) => {
+                let ty = lhs.ty(env)?.promote();
+
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("push %rax");
                 lhs.code_gen(lt, em, env)?;
                 em.emit_instr("pop %rcx");
-                em.emit_instr("shl %rcx, %rax");
+                em.emit_instr("shl %cl, %rax");
+                emit_convert(em, &ty);
             }
 
+            // A signed value is extended with its sign bit, so shifting the whole register
+            // arithmetically is right for any width
             (Expr::Binary(lhs, BinaryOperator::RightShift, rhs), _) => {
+                let ty = lhs.ty(env)?.promote();
+
                 rhs.code_gen(lt, em, env)?;
                 em.emit_instr("push %rax");
                 lhs.code_gen(lt, em, env)?;
                 em.emit_instr("pop %rcx");
-                em.emit_instr("shr %rcx, %rax");
+                em.emit_instr(if ty.is_signed() {
+                    "sar %cl, %rax"
+                } else {
+                    "shr %cl, %rax"
+                });
             }
 
             (Expr::Binary(lhs, BinaryOperator::Eq, rhs), _) => {
-                let var = lhs
+                let lvalue = lhs
                     .0
-                    .as_lvalue()
+                    .as_lvalue(env)
                     .ok_or((CodegenError::InvalidAssignmentTarget, lhs.1))?;
 
-                let (var_offset, _) = env
-                    .get(var)
-                    .ok_or((CodegenError::UndeclaredVariable(var), lhs.1))?;
+                let ty = lhs.ty(env)?;
+                if ty.is_array() || ty.is_function() {
+                    return Err((CodegenError::InvalidAssignmentTarget, lhs.1));
+                }
+                check_writable(&lhs, env)?;
+                let rhs_ty = rhs.ty(env)?.decay();
+                if !converts(&ty, &rhs_ty, &rhs, env) {
+                    return Err((CodegenError::IncompatibleTypes(ty, rhs_ty), rhs.1));
+                }
+
+                match lvalue {
+                    LValue::Variable(name) if !ty.is_aggregate() => {
+                        let var = env
+                            .get(name)
+                            .ok_or((CodegenError::UndeclaredVariable(name), lhs.1))?;
+
+                        rhs.code_gen(lt, em, env)?;
+                        emit_cast(em, lt, &rhs_ty, &ty);
+                        emit_store_scalar(em, &ty, &var.location.to_string());
+                    }
+                    lvalue => {
+                        (lvalue, lhs.1).code_gen(lt, em, env)?;
+                        em.emit_instr("push %rax");
+                        rhs.code_gen(lt, em, env)?;
+                        emit_cast(em, lt, &rhs_ty, &ty);
+                        em.emit_instr("pop %rcx");
+                        // Structs are copied whole, the result being the assigned object
+                        if ty.is_aggregate() {
+                            emit_copy(em, ty.size());
+                            em.emit_instr("mov %rcx, %rax");
+                        } else {
+                            emit_store_scalar(em, &ty, "(%rcx)");
+                        }
+                    }
+                }
+            }
 
+            // Only the value of the right operand is kept
+            (Expr::Binary(lhs, BinaryOperator::Comma, rhs), _) => {
+                lhs.code_gen(lt, em, env)?;
+                rhs.code_gen(lt, em, env)?;
+            }
+
+            // `a op= b` is `a = a op b`, except that the address of `a` is only computed
+            // once. It is kept on the stack while `b` is evaluated.
+            (Expr::Binary(lhs, op, rhs), _) if op.is_compound_assignment() => {
+                let (ty, rhs_ty) = (lhs.ty(env)?, rhs.ty(env)?.decay());
+                let lvalue = lhs
+                    .0
+                    .as_lvalue(env)
+                    .filter(|_| !ty.is_array() && !ty.is_function())
+                    .ok_or((CodegenError::InvalidAssignmentTarget, lhs.1))?;
+                check_writable(&lhs, env)?;
+                let op = op.compound_to_operator().expect("infallible");
+                let float = matches!(
+                    op,
+                    BinaryOperator::Plus
+                        | BinaryOperator::Minus
+                        | BinaryOperator::Multiply
+                        | BinaryOperator::Divide
+                ) && float_operands(&lhs, &rhs, env);
+
+                (lvalue, lhs.1).code_gen(lt, em, env)?;
+                em.emit_instr("push %rax");
                 rhs.code_gen(lt, em, env)?;
-                em.emit_instr(&format!("mov %rax, {}(%rbp)", var_offset));
+                if float {
+                    // The operation is made in the common type, then converted back to the
+                    // type of the target
+                    let common = ty.common(&rhs_ty);
+                    emit_cast(em, lt, &rhs_ty, &common);
+                    em.emit_instr("push %rax");
+                    em.emit_instr("mov 8(%rsp), %rax");
+                    emit_load(em, &ty);
+                    emit_cast(em, lt, &ty, &common);
+                    em.emit_instr("pop %rcx");
+                    em.emit_instr("movq %rax, %xmm0");
+                    em.emit_instr("movq %rcx, %xmm1");
+                    let sse_op = match op {
+                        BinaryOperator::Plus => "add",
+                        BinaryOperator::Minus => "sub",
+                        BinaryOperator::Multiply => "mul",
+                        _ => "div",
+                    };
+                    emit_sse_op(em, sse_op, &common);
+                    emit_cast(em, lt, &common, &ty);
+                } else {
+                    em.emit_instr("mov %rax, %rcx");
+                    em.emit_instr("mov (%rsp), %rax");
+                    emit_load(em, &ty);
+                    emit_operator(em, op, &ty, &rhs_ty);
+                }
+                em.emit_instr("pop %rcx");
+                emit_store_scalar(em, &ty, "(%rcx)");
+                emit_convert(em, &ty);
+            }
+
+            // The operand is only inspected for its type, never evaluated
+            (Expr::SizeOf(rhs), _) => {
+                em.emit_instr(&format!("mov ${}, %rax", rhs.ty(env)?.size()));
+            }
+
+            (Expr::SizeOfType(ty), span) => {
+                let ty = complete_type(&ty, env, span)?;
+                em.emit_instr(&format!("mov ${}, %rax", ty.size()));
+            }
+
+            (Expr::AlignOf(ty), span) => {
+                let ty = complete_type(&ty, env, span)?;
+                em.emit_instr(&format!("mov ${}, %rax", ty.align()));
             }
 
-            (Expr::Binary(lhs, op, rhs), span) if op.is_compound_assignment() => {
-                (Expr::Binary(lhs, op, rhs), span)
-                    .desugar()
-                    .expect("infallible")
-                    .code_gen(lt, em, env)?
+            // Only scalars can be converted, structs have no value to convert
+            (Expr::Cast(ty, rhs), span) => {
+                let to = ty.lookup(env, span)?.unqualified().clone();
+                let from = rhs.ty(env)?.decay();
+                if !cast_allowed(&from, &to) {
+                    return Err((CodegenError::InvalidCast(from, to), span));
+                }
+                rhs.code_gen(lt, em, env)?;
+                emit_cast(em, lt, &from, &to);
             }
 
+            (Expr::VaStart(ap, param), span) => {
+                let area = env
+                    .reg_save_area
+                    .ok_or((CodegenError::VaStartOutsideVariadic, span))?;
+                check_va_list(&ap, env)?;
+                match (param.0, area.last_param) {
+                    (Expr::Variable(name), Some(last)) if name == last => {}
+                    (_, last) => return Err((CodegenError::NotLastParam(last), param.1)),
+                }
+
+                ap.code_gen(lt, em, env)?;
+                em.emit_instr(&format!("movl ${}, (%rax)", area.gp_offset));
+                em.emit_instr(&format!("movl ${}, 4(%rax)", area.fp_offset));
+                em.emit_instr(&format!("lea {}(%rbp), %rcx", area.overflow));
+                em.emit_instr("mov %rcx, 8(%rax)");
+                em.emit_instr(&format!("lea {}(%rbp), %rcx", area.offset));
+                em.emit_instr("mov %rcx, 16(%rax)");
+                em.emit_instr("mov $0, %rax");
+            }
+
+            // The next argument is taken from the saved registers of its class until they
+            // run out, then from the stack
+            (Expr::VaArg(ap, ty), span) => {
+                let ty = complete_type(&ty, env, span)?;
+                if ty.is_aggregate() {
+                    return Err((CodegenError::InvalidVaArg(ty), span));
+                }
+                check_va_list(&ap, env)?;
+
+                let gp_end = ARG_REGISTERS.len() * 8;
+                let (field, limit, step) = match ty.is_float() {
+                    true => (4, gp_end + SSE_ARG_REGISTERS.len() * 16, 16),
+                    false => (0, gp_end, 8),
+                };
+                let stack = lt.create(LabelKind::VaArgStack);
+                let end = lt.create(LabelKind::VaArgEnd);
+
+                ap.code_gen(lt, em, env)?;
+                em.emit_instr("mov %rax, %rcx");
+                em.emit_instr(&format!("mov {}(%rcx), %eax", field));
+                em.emit_instr(&format!("cmp ${}, %eax", limit));
+                em.emit_instr(&format!("jae {}", stack));
+                em.emit_instr(&format!("lea {}(%rax), %edx", step));
+                em.emit_instr(&format!("mov %edx, {}(%rcx)", field));
+                em.emit_instr("add 16(%rcx), %rax");
+                em.emit_instr(&format!("jmp {}", end));
+                em.emit_label(&stack);
+                em.emit_instr("mov 8(%rcx), %rax");
+                em.emit_instr("lea 8(%rax), %rdx");
+                em.emit_instr("mov %rdx, 8(%rcx)");
+                em.emit_label(&end);
+                emit_load_scalar(em, &ty, "(%rax)");
+            }
+
+            // Nothing needs cleaning up
+            (Expr::VaEnd(ap), _) => {
+                check_va_list(&ap, env)?;
+                em.emit_instr("mov $0, %rax");
+            }
+
+            (Expr::Index(array, index), span) => (Expr::Index(array, index), span)
+                .desugar()
+                .expect("infallible")
+                .code_gen(lt, em, env)?,
+
             (Expr::Binary(_, _, _), _) => unreachable!("reached binary _ branch in codegen"),
 
             (Expr::Ternary(condition, a, b), _span) => {
                 let els = lt.create(LabelKind::TernaryElse);
                 let end = lt.create(LabelKind::TernaryEnd);
 
+                // Both arms are converted to their common type, whichever one is taken
+                let (a_ty, b_ty) = (a.ty(env)?.decay(), b.ty(env)?.decay());
+                let ty = match a_ty.is_arithmetic() && b_ty.is_arithmetic() {
+                    true => a_ty.common(&b_ty),
+                    false => a_ty.clone(),
+                };
+
+                let cond_ty = condition_ty(&condition, env)?;
                 condition.code_gen(lt, em, env)?;
-                em.emit_instr("cmp $0, %rax");
+                emit_cmp_zero(em, &cond_ty);
                 em.emit_instr(&format!("je {}", els));
                 a.code_gen(lt, em, env)?;
+                emit_cast(em, lt, &a_ty, &ty);
                 em.emit_instr(&format!("jmp {}", end));
                 em.emit_label(&els);
                 b.code_gen(lt, em, env)?;
+                emit_cast(em, lt, &b_ty, &ty);
                 em.emit_label(&end);
             }
+
+            (Expr::Call(callee, args), span) => {
+                // A name that is not a variable calls the function of that name, even one
+                // that was never declared. Anything else must point to a function.
+                let (name, function, pointer) = match *callee {
+                    (Expr::Variable(name), _) if !env.contains(name) => {
+                        (Some(name), env.get_function(name), None)
+                    }
+                    callee => {
+                        let invalid = (CodegenError::InvalidCallTarget, callee.1);
+                        let Type::Pointer(ty) = callee.ty(env)?.decay() else {
+                            return Err(invalid);
+                        };
+                        let Type::Function(ret, params, variadic) = *ty else {
+                            return Err(invalid);
+                        };
+                        let function = Function {
+                            ret: *ret,
+                            params: params.into_iter().map(|ty| (ty, callee.1)).collect(),
+                            variadic,
+                            span: callee.1,
+                            definition: None,
+                            external: true,
+                        };
+                        (None, Some(function), Some(callee))
+                    }
+                };
+
+                let ret = function
+                    .as_ref()
+                    .map_or(Type::Int { signed: true }, |f| f.ret.clone());
+                let arg_tys = args
+                    .iter()
+                    .map(|arg| arg.ty(env).map(Type::decay))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // Arguments are converted to the parameter types of a declared function.
+                // Variable arguments and those to undeclared functions are only promoted.
+                let param_tys = match function {
+                    Some(function) => {
+                        if args.len() < function.params.len()
+                            || (!function.variadic && args.len() != function.params.len())
+                        {
+                            return Err((
+                                CodegenError::ArgumentCount(
+                                    name,
+                                    function.params.len(),
+                                    function.span,
+                                ),
+                                span,
+                            ));
+                        }
+                        let mut param_tys = vec![];
+                        for ((arg, arg_ty), (ty, param_span)) in
+                            args.iter().zip(&arg_tys).zip(function.params)
+                        {
+                            if !converts(&ty, arg_ty, arg, env) {
+                                return Err((
+                                    CodegenError::InvalidArgument(ty, arg_ty.clone(), param_span),
+                                    arg.1,
+                                ));
+                            }
+                            param_tys.push(ty);
+                        }
+                        param_tys.extend(arg_tys[param_tys.len()..].iter().map(promote_argument));
+                        param_tys
+                    }
+                    None => arg_tys.iter().map(promote_argument).collect(),
+                };
+
+                // The address of a function called through a pointer is kept below the
+                // arguments
+                let indirect = pointer.is_some();
+                if let Some(pointer) = pointer {
+                    pointer.code_gen(lt, em, env)?;
+                    em.emit_instr("push %rax");
+                }
+
+                // Arguments are pushed right to left, leaving the first one on top. Structs
+                // are pushed a word at a time, so their words end up in memory order.
+                for ((arg, arg_ty), ty) in args.into_iter().zip(&arg_tys).zip(&param_tys).rev() {
+                    arg.code_gen(lt, em, env)?;
+                    emit_cast(em, lt, arg_ty, ty);
+                    if ty.is_aggregate() {
+                        em.emit_instr("mov %rax, %rcx");
+                        for offset in (0..ty.size()).step_by(8).rev() {
+                            emit_load_eightbyte(em, "%rcx", offset, ty.size(), "%r11");
+                            em.emit_instr("push %r11");
+                        }
+                    } else {
+                        em.emit_instr("push %rax");
+                    }
+                }
+
+                // Each argument goes in registers if all of it fits, otherwise on the stack.
+                // A struct returned in memory takes %rdi for its address.
+                let ret_in_memory = ret.is_aggregate() && classify(&ret) == [ArgClass::Memory];
+                let (mut ints, mut sses) = (ret_in_memory as usize, 0);
+                let mut reg_words = vec![];
+                let mut stack_words = vec![];
+                let mut pushed = 0;
+                for ty in &param_tys {
+                    let classes = classify(ty);
+                    let words = slot_size(ty) / WORD_IN_BYTES;
+                    let offsets = (pushed..pushed + words).map(|word| word * WORD_IN_BYTES);
+                    match assign_registers(
+                        &classes,
+                        (&ARG_REGISTERS, &mut ints),
+                        (&SSE_ARG_REGISTERS, &mut sses),
+                    ) {
+                        Some(regs) => reg_words.extend(offsets.zip(regs)),
+                        None => stack_words.extend(offsets),
+                    }
+                    pushed += words;
+                }
+
+                // Temporaries make the depth of %rsp unknown here, so the stack arguments
+                // are copied into a 16 byte aligned area with the old %rsp saved above them
+                let args_size = stack_words.len() as isize * WORD_IN_BYTES;
+                em.emit_instr("mov %rsp, %rax");
+                em.emit_instr(&format!("sub ${}, %rsp", args_size + WORD_IN_BYTES));
+                em.emit_instr("and $-16, %rsp");
+                for (i, offset) in stack_words.into_iter().enumerate() {
+                    em.emit_instr(&format!("mov {}(%rax), %r11", offset));
+                    em.emit_instr(&format!("mov %r11, {}(%rsp)", i as isize * WORD_IN_BYTES));
+                }
+                em.emit_instr(&format!("mov %rax, {}(%rsp)", args_size));
+                if indirect {
+                    em.emit_instr(&format!("mov {}(%rax), %r10", pushed * WORD_IN_BYTES));
+                }
+                for (offset, reg) in reg_words {
+                    let mov = if reg.starts_with("%xmm") {
+                        "movq"
+                    } else {
+                        "mov"
+                    };
+                    em.emit_instr(&format!("{} {}(%rax), {}", mov, offset, reg));
+                }
+
+                let temp = ret.is_aggregate().then(|| env.put_temp(&ret));
+                if let (true, Some(temp)) = (ret_in_memory, temp) {
+                    em.emit_instr(&format!("lea {}(%rbp), %rdi", temp));
+                }
+
+                // A variadic callee reads the number of vector registers used from %al
+                em.emit_instr(&format!("mov ${}, %eax", sses));
+                match name {
+                    Some(name) => em.emit_instr(&format!("call {}", name)),
+                    None => em.emit_instr("call *%r10"),
+                }
+                em.emit_instr(&format!("mov {}(%rsp), %rsp", args_size));
+                let pushed = pushed + indirect as isize;
+                if pushed > 0 {
+                    em.emit_instr(&format!("add ${}, %rsp", pushed * WORD_IN_BYTES));
+                }
+
+                // A struct returned in registers is stored into a temporary, so that like
+                // any other struct it is handled by address
+                if let Some(temp) = temp {
+                    if !ret_in_memory {
+                        let regs = assign_registers(
+                            &classify(&ret),
+                            (&RET_REGISTERS, &mut 0),
+                            (&SSE_RET_REGISTERS, &mut 0),
+                        )
+                        .expect("infallible");
+                        for (i, reg) in regs.into_iter().enumerate().rev() {
+                            emit_store_eightbyte(
+                                em,
+                                reg,
+                                "%rbp",
+                                temp + i as isize * WORD_IN_BYTES,
+                            );
+                        }
+                    }
+                    em.emit_instr(&format!("lea {}(%rbp), %rax", temp));
+                }
+
+                // Only the low bytes of a narrow return value are defined
+                match ret.is_float() {
+                    true => emit_from_xmm0(em, &ret),
+                    false => emit_convert(em, &ret),
+                }
+            }
         }
         Ok(())
     }
 }
 
+/// Checks that `ap` is a `va_list`, or a parameter declared as one
+fn check_va_list<'src>(
+    ap: &Spanned<Expr<'src>>,
+    env: &Environment<'src>,
+) -> Result<(), Spanned<CodegenError<'src>>> {
+    match ap.ty(env)?.decay() {
+        ty if ty == env.va_list().decay() => Ok(()),
+        ty => Err((CodegenError::InvalidVaList(ty), ap.1)),
+    }
+}
+
+/// Applies the default argument promotions, for arguments without a parameter type
+fn promote_argument(ty: &Type) -> Type {
+    match ty {
+        Type::Float => Type::Double,
+        ty => ty.clone().promote(),
+    }
+}
+
+/// Looks up a type name whose layout is needed, which only a complete type has
+pub fn complete_type<'src>(
+    ty: &TypeSpec<'src>,
+    env: &Environment<'src>,
+    span: Span,
+) -> Result<Type, Spanned<CodegenError<'src>>> {
+    match ty.lookup(env, span)? {
+        ty if ty.is_complete() => Ok(ty),
+        ty => Err((CodegenError::IncompleteType(ty), span)),
+    }
+}
+
+/// Whether a value of type `from` can be cast to `to`. Pointers convert to and from
+/// integers, but not floating point values. Anything can be cast to `void`, discarding it.
+fn cast_allowed(from: &Type, to: &Type) -> bool {
+    match (from, to) {
+        (_, Type::Void) => true,
+        _ if from.is_aggregate() || to.is_aggregate() || from.is_void() => false,
+        (Type::Pointer(_), to) | (to, Type::Pointer(_)) => !to.is_float(),
+        _ => true,
+    }
+}
+
+/// Replaces the address in `%rax` with the value it points to, unless the value is an
+/// aggregate or a function which are handled by address
+fn emit_load(em: &mut Emitter, ty: &Type) {
+    if !ty.is_aggregate() && !ty.is_function() {
+        emit_load_scalar(em, ty, "(%rax)");
+    }
+}
+
+/// Loads a 64-bit constant into `%rax`. Only `movabs` takes an immediate that does not fit
+/// in 32 bits.
+fn emit_immediate(em: &mut Emitter, i: u64) {
+    match i32::try_from(i as i64) {
+        Ok(i) => em.emit_instr(&format!("mov ${}, %rax", i)),
+        Err(_) => em.emit_instr(&format!("movabs ${}, %rax", i as i64)),
+    }
+}
+
+/// Whether an arithmetic operator on `lhs` and `rhs` operates on floating point values
+fn float_operands<'src>(
+    lhs: &Spanned<Expr<'src>>,
+    rhs: &Spanned<Expr<'src>>,
+    env: &Environment<'src>,
+) -> bool {
+    match (lhs.ty(env), rhs.ty(env)) {
+        (Ok(lhs), Ok(rhs)) => {
+            lhs.is_arithmetic() && rhs.is_arithmetic() && lhs.common(&rhs).is_float()
+        }
+        _ => false,
+    }
+}
+
+/// Applies the SSE operation `op` to `%xmm0` and `%xmm1` of type `ty`, leaving the result in
+/// `%rax`
+fn emit_sse_op(em: &mut Emitter, op: &str, ty: &Type) {
+    em.emit_instr(&format!("{}{} %xmm1, %xmm0", op, sse_suffix(ty)));
+    emit_from_xmm0(em, ty);
+}
+
+/// Divides `%rax` by `%rcx` as integers of type `ty`, leaving the quotient in `%rax` and the
+/// remainder in `%rdx`
+fn emit_divide(em: &mut Emitter, ty: &Type) {
+    if ty.is_signed() {
+        em.emit_instr("cqo");
+        em.emit_instr("idiv %rcx");
+    } else {
+        em.emit_instr("xor %edx, %edx");
+        em.emit_instr("div %rcx");
+    }
+}
+
+/// Multiplies the integer in `reg` by the size of `pointee`, turning an element count into
+/// a byte offset
+fn emit_scale(em: &mut Emitter, reg: &str, pointee: &Type) {
+    if pointee.size() != 1 {
+        em.emit_instr(&format!("imul ${}, {}", pointee.size(), reg));
+    }
+}
+
+/// Applies the arithmetic operator `op` of a compound assignment to `%rax`, holding the
+/// target of type `ty`, and `%rcx`, holding the right operand of type `rhs_ty`
+fn emit_operator(em: &mut Emitter, op: BinaryOperator, ty: &Type, rhs_ty: &Type) {
+    if let (Some(pointee), false) = (ty.pointee(), rhs_ty.is_pointer()) {
+        emit_scale(em, "%rcx", pointee);
+    }
+    match op {
+        BinaryOperator::Plus => em.emit_instr("add %rcx, %rax"),
+        BinaryOperator::Minus => em.emit_instr("sub %rcx, %rax"),
+        BinaryOperator::Multiply => em.emit_instr("imul %rcx, %rax"),
+        // Division depends on the width of its operands, so both are first converted
+        BinaryOperator::Divide | BinaryOperator::Mod => {
+            let common = ty.common(rhs_ty);
+            emit_convert(em, &common);
+            em.emit_instr("xchg %rax, %rcx");
+            emit_convert(em, &common);
+            em.emit_instr("xchg %rax, %rcx");
+            emit_divide(em, &common);
+            if op == BinaryOperator::Mod {
+                em.emit_instr("mov %rdx, %rax");
+            }
+        }
+        BinaryOperator::BitwiseAnd => em.emit_instr("and %rcx, %rax"),
+        BinaryOperator::BitwiseOr => em.emit_instr("or %rcx, %rax"),
+        BinaryOperator::BitwiseXor => em.emit_instr("xor %rcx, %rax"),
+        BinaryOperator::LeftShift => em.emit_instr("shl %cl, %rax"),
+        BinaryOperator::RightShift if ty.clone().promote().is_signed() => {
+            em.emit_instr("sar %cl, %rax")
+        }
+        BinaryOperator::RightShift => em.emit_instr("shr %cl, %rax"),
+        _ => unreachable!("not the operator of a compound assignment"),
+    }
+}
+
 impl<'src> Desugar<Spanned<Expr<'src>>> for Spanned<Expr<'src>> {
     fn desugar(self) -> Option<Vec<Spanned<Expr<'src>>>> {
         Some(match self {
-            (Expr::Binary(lhs, op, rhs), span) if op.is_compound_assignment() => {
-                vec![(
-                    Expr::Binary(
-                        lhs.clone(),
-                        BinaryOperator::Eq,
-                        Box::new((Expr::Binary(lhs, op.compound_to_operator()?, rhs), span)),
-                    ),
+            // a[i] is *(a + i)
+            (Expr::Index(array, index), span) => {
+                vec![Expr::new_unary(
+                    UnaryOperator::Deref,
+                    Expr::new_binary(*array, BinaryOperator::Plus, *index, span),
                     span,
                 )]
             }
@@ -319,6 +1092,26 @@ impl<'src> Expr<'src> {
         (Expr::Binary(Box::new(lhs), op, Box::new(rhs)), span)
     }
 
+    pub fn new_call(callee: Spanned<Self>, args: Vec<Spanned<Self>>, span: Span) -> Spanned<Self> {
+        (Expr::Call(Box::new(callee), args), span)
+    }
+
+    pub fn new_index(array: Spanned<Self>, index: Spanned<Self>, span: Span) -> Spanned<Self> {
+        (Expr::Index(Box::new(array), Box::new(index)), span)
+    }
+
+    pub fn new_sizeof(rhs: Spanned<Self>, span: Span) -> Spanned<Self> {
+        (Expr::SizeOf(Box::new(rhs)), span)
+    }
+
+    pub fn new_cast(ty: TypeSpec<'src>, rhs: Spanned<Self>, span: Span) -> Spanned<Self> {
+        (Expr::Cast(ty, Box::new(rhs)), span)
+    }
+
+    pub fn new_member(lhs: Spanned<Self>, name: Spanned<&'src str>, span: Span) -> Spanned<Self> {
+        (Expr::Member(Box::new(lhs), name), span)
+    }
+
     pub fn new_ternary(
         cond: Spanned<Self>,
         a: Spanned<Self>,
@@ -332,25 +1125,445 @@ impl<'src> Expr<'src> {
     }
 }
 
+/// An expression designating an object in memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum LValue<'src> {
+    Variable(&'src str),
+    Deref(Spanned<Expr<'src>>),
+    Member(Spanned<Expr<'src>>, Spanned<&'src str>),
+}
+
 impl<'src> Expr<'src> {
-    pub fn as_lvalue(&self) -> Option<&'src str> {
+    /// Enumerators are spelled like variables but are constants without storage, so `env`
+    /// is needed to tell them apart
+    pub fn as_lvalue(&self, env: &Environment<'src>) -> Option<LValue<'src>> {
         match self {
-            Expr::Variable(s) => Some(s),
+            Expr::Variable(s) => match env.get_symbol(s) {
+                Some(Symbol::Enumerator(_, _)) => None,
+                _ => Some(LValue::Variable(s)),
+            },
+            Expr::Unary(UnaryOperator::Deref, rhs) => Some(LValue::Deref((**rhs).clone())),
+            Expr::Index(array, index) => (self.clone(), Span::new(array.1.start, index.1.end))
+                .desugar()?
+                .pop()?
+                .0
+                .as_lvalue(env),
+            Expr::Member(lhs, name) => {
+                lhs.0.as_lvalue(env)?;
+                Some(LValue::Member((**lhs).clone(), *name))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether `value`, of type `from` once decayed, converts implicitly to `to` as when it is
+/// assigned, passed or returned. An integer constant zero is also a null pointer.
+pub fn converts<'src>(
+    to: &Type,
+    from: &Type,
+    value: &Spanned<Expr<'src>>,
+    env: &Environment<'src>,
+) -> bool {
+    let is_null = to.is_pointer() && from.is_integer() && matches!(value.eval(env), Ok(0));
+    to.accepts(from) || is_null
+}
+
+/// Fails if the object `lhs` designates, which is about to be written, is `const` or is a
+/// struct with a `const` member. The error points at the declaration of the variable the
+/// object is reached through as well.
+fn check_writable<'src>(
+    lhs: &Spanned<Expr<'src>>,
+    env: &Environment<'src>,
+) -> Result<(), Spanned<CodegenError<'src>>> {
+    let ty = object_ty(lhs, env)?;
+    match ty.qualifiers().is_const || ty.has_const_member() {
+        true => Err((
+            CodegenError::ConstAssignment(ty, declaration_of(lhs, env)),
+            lhs.1,
+        )),
+        false => Ok(()),
+    }
+}
+
+/// The variable whose declaration the object `expr` designates is reached through, following
+/// pointers and members back to it
+fn declaration_of<'src>(
+    expr: &Spanned<Expr<'src>>,
+    env: &Environment<'src>,
+) -> Option<Spanned<&'src str>> {
+    let is_pointer =
+        |expr: &Spanned<Expr<'src>>| expr.ty(env).is_ok_and(|ty| ty.decay().is_pointer());
+    match &expr.0 {
+        Expr::Variable(name) => match env.get_symbol(name) {
+            Some(Symbol::Variable(var)) => Some((*name, var.span)),
             _ => None,
+        },
+        Expr::Unary(UnaryOperator::Deref, rhs) | Expr::Member(rhs, _) => declaration_of(rhs, env),
+        Expr::Index(lhs, rhs)
+        | Expr::Binary(lhs, BinaryOperator::Plus | BinaryOperator::Minus, rhs) => {
+            match is_pointer(lhs) || !is_pointer(rhs) {
+                true => declaration_of(lhs, env),
+                false => declaration_of(rhs, env),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Loads the address of the designated object into `%rax`
+impl<'src> Codegen<'src> for Spanned<LValue<'src>> {
+    fn code_gen(
+        self,
+        lt: &mut LabelTracker,
+        em: &mut Emitter,
+        env: &mut Environment<'src>,
+    ) -> Result<(), Spanned<CodegenError<'src>>> {
+        match self {
+            (LValue::Variable(name), span) => match env.get_symbol(name) {
+                Some(Symbol::Variable(var)) => {
+                    em.emit_instr(&format!("lea {}, %rax", var.location))
+                }
+                None if env.get_function(name).is_some() => {
+                    em.emit_instr(&format!("lea {}(%rip), %rax", name))
+                }
+                _ => return Err((CodegenError::UndeclaredVariable(name), span)),
+            },
+
+            (LValue::Deref(ptr), span) => {
+                let ty = ptr.ty(env)?.decay();
+                if !ty.is_pointer() {
+                    return Err((CodegenError::InvalidDereference(ty), span));
+                }
+
+                ptr.code_gen(lt, em, env)?;
+            }
+
+            (LValue::Member(lhs, name), _) => {
+                let member = member_of(&lhs, name, env)?;
+
+                lhs.code_gen(lt, em, env)?;
+                if member.offset != 0 {
+                    em.emit_instr(&format!("add ${}, %rax", member.offset));
+                }
+            }
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::{
+        env::RegSaveArea,
+        ty::{Qualifiers, StructRef, TagKind},
+    };
 
     #[test]
     fn test_as_lvalue() {
+        let mut env = Environment::new();
+        env.new_scope();
+
         let expr = Expr::Variable("x");
-        assert_eq!(expr.as_lvalue(), Some("x"));
+        assert_eq!(expr.as_lvalue(&env), Some(LValue::Variable("x")));
+
+        let expr = Expr::LiteralInteger(42, Type::Int { signed: true });
+        assert_eq!(expr.as_lvalue(&env), None);
+
+        let ptr = (Expr::Variable("p"), Span::new(1, 2));
+        let expr = Expr::new_unary(UnaryOperator::Deref, ptr.clone(), Span::new(0, 2)).0;
+        assert_eq!(expr.as_lvalue(&env), Some(LValue::Deref(ptr)));
+
+        let expr = Expr::new_unary(
+            UnaryOperator::AddressOf,
+            (Expr::Variable("x"), Span::new(1, 2)),
+            Span::new(0, 2),
+        )
+        .0;
+        assert_eq!(expr.as_lvalue(&env), None);
+
+        // Enumerators have no storage
+        env.put_enumerator("E", 1, Span::new(0, 1));
+        assert_eq!(Expr::Variable("E").as_lvalue(&env), None);
+    }
+
+    #[test]
+    fn compound_assignment() {
+        let mut env = Environment::new();
+        env.new_scope();
+        let span = Span::new(0, 1);
+        env.put("p", Type::Int { signed: true }.pointer_to(), span);
+
+        // *(p + f()) += 5
+        let call = Expr::new_call((Expr::Variable("f"), span), vec![], span);
+        let sum = Expr::new_binary(
+            (Expr::Variable("p"), span),
+            BinaryOperator::Plus,
+            call,
+            span,
+        );
+        let target = Expr::new_unary(UnaryOperator::Deref, sum, span);
+        let five = (Expr::LiteralInteger(5, Type::Int { signed: true }), span);
+        let expr = Expr::new_binary(target, BinaryOperator::PlusEquals, five, span);
+
+        let mut em = Emitter::new();
+        expr.code_gen(&mut LabelTracker::new(), &mut em, &mut env)
+            .unwrap();
+
+        // The address of the target, and so `f()`, is only evaluated once
+        assert_eq!(em.collect().matches("call f").count(), 1);
+    }
+
+    #[test]
+    fn va_start_param() {
+        let mut env = Environment::new();
+        env.new_scope();
+        let span = Span::new(0, 1);
+        let int = Type::Int { signed: true };
+        env.put("n", int.clone(), span);
+        env.put("x", int, span);
+        env.put("ap", env.va_list(), span);
+        env.reg_save_area = Some(RegSaveArea {
+            offset: -176,
+            gp_offset: 8,
+            fp_offset: 48,
+            overflow: 16,
+            last_param: Some("n"),
+        });
+
+        let va_start = |param| {
+            (
+                Expr::VaStart(
+                    Box::new((Expr::Variable("ap"), span)),
+                    Box::new((Expr::Variable(param), span)),
+                ),
+                span,
+            )
+        };
+        let mut code_gen = |expr: Spanned<Expr<'static>>| {
+            expr.code_gen(&mut LabelTracker::new(), &mut Emitter::new(), &mut env)
+        };
+
+        assert!(code_gen(va_start("n")).is_ok());
+        assert!(matches!(
+            code_gen(va_start("x")),
+            Err((CodegenError::NotLastParam(Some("n")), _))
+        ));
+    }
+
+    #[test]
+    fn assignment_conversions() {
+        let mut env = Environment::new();
+        env.new_scope();
+        let span = Span::new(0, 1);
+        let int = Type::Int { signed: true };
+        env.put("p", int.clone().pointer_to(), span);
+        env.put("q", int.qualified(Qualifiers::CONST).pointer_to(), span);
+
+        let assign =
+            |rhs| Expr::new_binary((Expr::Variable("p"), span), BinaryOperator::Eq, rhs, span);
+        let mut code_gen = |expr: Spanned<Expr<'static>>| {
+            expr.code_gen(&mut LabelTracker::new(), &mut Emitter::new(), &mut env)
+        };
+
+        // A null pointer constant converts to any pointer, but `const` can't be dropped
+        let zero = (Expr::LiteralInteger(0, Type::Int { signed: true }), span);
+        assert!(code_gen(assign(zero)).is_ok());
+        assert!(matches!(
+            code_gen(assign((Expr::Variable("q"), span))),
+            Err((CodegenError::IncompatibleTypes(_, _), _))
+        ));
+    }
+
+    #[test]
+    fn integer_operators() {
+        let mut env = Environment::new();
+        env.new_scope();
+        let span = Span::new(0, 1);
+        env.put("n", Type::Int { signed: true }, span);
+        env.put("x", Type::Double, span);
+
+        let mut code_gen = |lhs, op, rhs| {
+            Expr::new_binary(
+                (Expr::Variable(lhs), span),
+                op,
+                (Expr::Variable(rhs), span),
+                span,
+            )
+            .code_gen(&mut LabelTracker::new(), &mut Emitter::new(), &mut env)
+        };
+
+        for (op, compound) in [
+            (BinaryOperator::Mod, BinaryOperator::ModEquals),
+            (BinaryOperator::BitwiseAnd, BinaryOperator::AndEquals),
+            (BinaryOperator::BitwiseOr, BinaryOperator::OrEquals),
+            (BinaryOperator::BitwiseXor, BinaryOperator::XorEquals),
+            (BinaryOperator::LeftShift, BinaryOperator::LeftShiftEquals),
+            (BinaryOperator::RightShift, BinaryOperator::RightShiftEquals),
+        ] {
+            assert!(code_gen("n", op, "n").is_ok());
+            assert!(code_gen("n", compound, "n").is_ok());
+            for (lhs, op, rhs) in [("x", op, "n"), ("n", op, "x"), ("n", compound, "x")] {
+                assert!(
+                    matches!(
+                        code_gen(lhs, op, rhs),
+                        Err((CodegenError::InvalidOperands(_, _), _))
+                    ),
+                    "{lhs} {op:?} {rhs}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pointer_arithmetic() {
+        let mut env = Environment::new();
+        env.new_scope();
+        let span = Span::new(0, 1);
+        let int = Type::Int { signed: true };
+        env.put("n", int.clone(), span);
+        env.put("x", Type::Double, span);
+        env.put("p", int.clone().pointer_to(), span);
+        env.put("q", int.pointer_to(), span);
+        env.put("c", Type::Char { signed: true }.pointer_to(), span);
+
+        let mut code_gen = |lhs, op, rhs| {
+            Expr::new_binary(
+                (Expr::Variable(lhs), span),
+                op,
+                (Expr::Variable(rhs), span),
+                span,
+            )
+            .code_gen(&mut LabelTracker::new(), &mut Emitter::new(), &mut env)
+        };
+
+        for (lhs, op, rhs) in [
+            ("p", BinaryOperator::Plus, "n"),
+            ("n", BinaryOperator::Plus, "p"),
+            ("p", BinaryOperator::Minus, "n"),
+            ("p", BinaryOperator::Minus, "q"),
+            ("p", BinaryOperator::PlusEquals, "n"),
+            ("p", BinaryOperator::MinusEquals, "n"),
+        ] {
+            assert!(code_gen(lhs, op, rhs).is_ok(), "{lhs} {op:?} {rhs}");
+        }
+
+        for (lhs, op, rhs) in [
+            ("p", BinaryOperator::Plus, "q"),
+            ("p", BinaryOperator::Plus, "x"),
+            ("n", BinaryOperator::Minus, "p"),
+            ("p", BinaryOperator::Minus, "c"),
+            ("p", BinaryOperator::Multiply, "n"),
+            ("n", BinaryOperator::Divide, "p"),
+            ("p", BinaryOperator::Mod, "n"),
+            ("p", BinaryOperator::BitwiseAnd, "n"),
+            ("n", BinaryOperator::BitwiseOr, "p"),
+            ("p", BinaryOperator::BitwiseXor, "n"),
+            ("p", BinaryOperator::LeftShift, "n"),
+            ("p", BinaryOperator::RightShift, "n"),
+            ("p", BinaryOperator::MinusEquals, "q"),
+            ("n", BinaryOperator::PlusEquals, "p"),
+            ("p", BinaryOperator::MultiplyEquals, "n"),
+        ] {
+            assert!(
+                matches!(
+                    code_gen(lhs, op, rhs),
+                    Err((CodegenError::InvalidOperands(_, _), _))
+                ),
+                "{lhs} {op:?} {rhs}"
+            );
+        }
+    }
+
+    #[test]
+    fn scalar_operands() {
+        let mut env = Environment::new();
+        env.new_scope();
+        let span = Span::new(0, 1);
+        let s = StructRef::new(TagKind::Struct, Some("s".into()));
+        s.define(vec![("a".into(), Type::Int { signed: true })]);
+        env.put("n", Type::Int { signed: true }, span);
+        env.put("s", Type::Struct(s), span);
+        let var = |name| (Expr::Variable(name), span);
+
+        let mut code_gen = |expr: Spanned<Expr<'static>>| {
+            expr.code_gen(&mut LabelTracker::new(), &mut Emitter::new(), &mut env)
+        };
+
+        for (lhs, op, rhs) in [
+            ("s", BinaryOperator::Plus, "n"),
+            ("n", BinaryOperator::Minus, "s"),
+            ("s", BinaryOperator::Multiply, "n"),
+            ("n", BinaryOperator::Divide, "s"),
+            ("s", BinaryOperator::LogicalAnd, "n"),
+            ("n", BinaryOperator::LogicalOr, "s"),
+            ("s", BinaryOperator::EqEq, "s"),
+            ("n", BinaryOperator::Lt, "s"),
+        ] {
+            assert!(
+                matches!(
+                    code_gen(Expr::new_binary(var(lhs), op, var(rhs), span)),
+                    Err((CodegenError::InvalidOperands(_, _), _))
+                ),
+                "{lhs} {op:?} {rhs}"
+            );
+        }
+
+        for op in [
+            UnaryOperator::LogicalNot,
+            UnaryOperator::Minus,
+            UnaryOperator::Plus,
+            UnaryOperator::BitwiseNot,
+        ] {
+            assert!(
+                matches!(
+                    code_gen(Expr::new_unary(op, var("s"), span)),
+                    Err((CodegenError::InvalidOperand(_), _))
+                ),
+                "{op:?}"
+            );
+        }
+
+        let ternary = |condition| {
+            (
+                Expr::Ternary(
+                    Box::new(var(condition)),
+                    Box::new(var("n")),
+                    Box::new(var("n")),
+                ),
+                span,
+            )
+        };
+        assert!(code_gen(ternary("n")).is_ok());
+        assert!(matches!(
+            code_gen(ternary("s")),
+            Err((CodegenError::NonScalarCondition(_), _))
+        ));
+    }
+
+    #[test]
+    fn void_values() {
+        let mut env = Environment::new();
+        env.new_scope();
+        let span = Span::new(0, 1);
+        env.put("p", Type::Void.pointer_to(), span);
+        let p = || (Expr::Variable("p"), span);
+
+        let mut code_gen = |expr: Spanned<Expr<'static>>| {
+            expr.code_gen(&mut LabelTracker::new(), &mut Emitter::new(), &mut env)
+        };
 
-        let expr = Expr::LiteralInteger(42);
-        assert_eq!(expr.as_lvalue(), None);
+        // Any value may be discarded by a cast to `void`, but a `void *` points to no value
+        assert!(code_gen(Expr::new_cast(TypeSpec::Void, p(), span)).is_ok());
+        assert!(matches!(
+            code_gen(Expr::new_unary(UnaryOperator::Deref, p(), span)),
+            Err((CodegenError::InvalidDereference(_), _))
+        ));
+        let value = Expr::new_cast(TypeSpec::Void, p(), span);
+        assert!(matches!(
+            code_gen(Expr::new_cast(TypeSpec::Int { signed: true }, value, span)),
+            Err((CodegenError::InvalidCast(Type::Void, _), _))
+        ));
     }
 }
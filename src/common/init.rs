@@ -0,0 +1,430 @@
+use super::{
+    env::Environment, ty::TagKind, CodegenError, ConstEval, Expr, Span, Spanned, Type, TypeOf,
+};
+use std::collections::VecDeque;
+
+/// The initializer of a declared object
+#[derive(Debug)]
+pub enum Initializer<'src> {
+    Expr(Spanned<Expr<'src>>),
+    /// A brace enclosed list, whose initializers may each be preceded by designators such as
+    /// the `.x` and `[3]` of `.x[3] = 7`
+    List(Vec<(Vec<Designator<'src>>, Spanned<Self>)>),
+}
+
+/// Picks the element of the object being initialized that the next initializer applies to
+#[derive(Debug)]
+pub enum Designator<'src> {
+    Member(Spanned<&'src str>),
+    /// The index must be an integer constant expression
+    Index(Spanned<Expr<'src>>),
+}
+
+impl Designator<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Member((_, span)) | Self::Index((_, span)) => *span,
+        }
+    }
+}
+
+/// A scalar, or a struct copied as a whole, that an initializer stores `offset` bytes into
+/// the object it initializes
+#[derive(Debug)]
+pub struct Element<'src> {
+    pub offset: isize,
+    pub ty: Type,
+    pub expr: Spanned<Expr<'src>>,
+}
+
+/// Lowers `init` to the elements it stores into an object of type `ty`, ordered by offset.
+/// Whatever no element covers is zero.
+pub fn lower<'src>(
+    ty: &Type,
+    init: &Spanned<Initializer<'src>>,
+    env: &Environment<'src>,
+) -> Result<Vec<Element<'src>>, Spanned<CodegenError<'src>>> {
+    let mut lowering = Lowering {
+        env,
+        elements: Vec::new(),
+    };
+    lowering.initialize(ty, 0, init)?;
+    lowering.elements.sort_by_key(|element| element.offset);
+    Ok(lowering.elements)
+}
+
+/// The length of an array of `elem` declared without one, taken from the highest index its
+/// initializer `init` gives a value. Only a brace enclosed list or a string has one.
+pub fn array_length<'src>(
+    elem: &Type,
+    init: &Spanned<Initializer<'src>>,
+    env: &Environment<'src>,
+) -> Result<Option<usize>, Spanned<CodegenError<'src>>> {
+    // Measured as an array too long for any initializer to fill
+    let unbounded = elem.clone().array_of(usize::MAX);
+    match &init.0 {
+        Initializer::Expr(expr) => Ok(string(&unbounded, expr).map(|bytes| bytes.len() + 1)),
+        Initializer::List(items) => match braced_string(&unbounded, items) {
+            Some(bytes) => Ok(Some(bytes.len() + 1)),
+            None => {
+                let mut lowering = Lowering {
+                    env,
+                    elements: Vec::new(),
+                };
+                let mut items = items.iter().map(|(d, init)| (d.as_slice(), init)).collect();
+                lowering.fill(&unbounded, 0, &mut items, true).map(Some)
+            }
+        },
+    }
+}
+
+/// The initializers of a list yet to be applied, each with the designators it has left
+type Items<'a, 'src> = VecDeque<(&'a [Designator<'src>], &'a Spanned<Initializer<'src>>)>;
+
+struct Lowering<'a, 'src> {
+    env: &'a Environment<'src>,
+    elements: Vec<Element<'src>>,
+}
+
+impl<'a, 'src> Lowering<'a, 'src> {
+    /// Initializes all of the object of type `ty` at `offset` from `init`
+    fn initialize(
+        &mut self,
+        ty: &Type,
+        offset: isize,
+        init: &'a Spanned<Initializer<'src>>,
+    ) -> Result<(), Spanned<CodegenError<'src>>> {
+        let items = match &init.0 {
+            Initializer::Expr(expr) => return self.expr(ty, offset, expr),
+            Initializer::List(items) => items,
+        };
+        if let Some(bytes) = braced_string(ty, items) {
+            return self.string(ty, offset, bytes, init.1);
+        }
+
+        let mut items: Items = items.iter().map(|(d, init)| (d.as_slice(), init)).collect();
+        match ty.is_aggregate() {
+            true => {
+                self.fill(ty, offset, &mut items, true)?;
+            }
+            // A scalar may be braced too, by itself
+            false => match items.pop_front() {
+                Some(([], init)) => self.initialize(ty, offset, init)?,
+                Some(([designator, ..], _)) => {
+                    return Err((
+                        CodegenError::InvalidDesignator(ty.clone()),
+                        designator.span(),
+                    ))
+                }
+                None => {}
+            },
+        }
+        match items.front() {
+            Some((_, (_, span))) => Err((CodegenError::ExcessInitializers(ty.clone()), *span)),
+            None => Ok(()),
+        }
+    }
+
+    /// Initializes the elements of the aggregate `ty` at `offset` in order from `items`,
+    /// returning one past the highest index it initialized. Without braces of its own, the
+    /// list ends once `ty` is full or at the next designator, which then applies to an
+    /// enclosing list.
+    fn fill(
+        &mut self,
+        ty: &Type,
+        offset: isize,
+        items: &mut Items<'a, 'src>,
+        braced: bool,
+    ) -> Result<usize, Spanned<CodegenError<'src>>> {
+        let union = matches!(ty.unqualified(), Type::Struct(s) if s.kind() == TagKind::Union);
+        let (mut index, mut len) = (0, 0);
+        let mut first = true;
+
+        while let Some(&(designators, _)) = items.front() {
+            match designators.split_first() {
+                Some(_) if !braced && !first => break,
+                Some((designator, rest)) => {
+                    index = self.designate(ty, designator)?;
+                    items[0].0 = rest;
+                }
+                // Only one member of a union is initialized, the first unless designated
+                None if union && !first => break,
+                None => {}
+            }
+            let Some((elem, elem_offset)) = sub_object(ty, index) else {
+                break;
+            };
+            self.element(&elem, offset + elem_offset, items)?;
+            index += 1;
+            len = len.max(index);
+            first = false;
+        }
+        Ok(len)
+    }
+
+    /// Initializes the element of type `ty` at `offset` from the front of `items`, taking as
+    /// many of them as it needs when the braces around its own initializers are left out
+    fn element(
+        &mut self,
+        ty: &Type,
+        offset: isize,
+        items: &mut Items<'a, 'src>,
+    ) -> Result<(), Spanned<CodegenError<'src>>> {
+        let (designators, init) = items[0];
+        if designators.is_empty() && !self.elided(ty, init)? {
+            items.pop_front();
+            return self.initialize(ty, offset, init);
+        }
+        match (ty.is_aggregate(), designators.first()) {
+            (true, _) => self.fill(ty, offset, items, false).map(|_| ()),
+            (false, Some(designator)) => Err((
+                CodegenError::InvalidDesignator(ty.clone()),
+                designator.span(),
+            )),
+            (false, None) => unreachable!("only aggregates have their braces left out"),
+        }
+    }
+
+    /// Whether `init` is the first initializer of the aggregate `ty` with the braces around
+    /// them left out, rather than the initializer of all of it
+    fn elided(
+        &self,
+        ty: &Type,
+        init: &Spanned<Initializer<'src>>,
+    ) -> Result<bool, Spanned<CodegenError<'src>>> {
+        Ok(match (&init.0, ty.unqualified()) {
+            (Initializer::List(_), _) => false,
+            (Initializer::Expr(expr), _) if string(ty, expr).is_some() => false,
+            (Initializer::Expr(expr), ty @ Type::Struct(_)) => expr.ty(self.env)? != *ty,
+            (Initializer::Expr(_), _) => ty.is_aggregate(),
+        })
+    }
+
+    /// Initializes the object of type `ty` at `offset` from the single expression `expr`
+    fn expr(
+        &mut self,
+        ty: &Type,
+        offset: isize,
+        expr: &Spanned<Expr<'src>>,
+    ) -> Result<(), Spanned<CodegenError<'src>>> {
+        if let Some(bytes) = string(ty, expr) {
+            return self.string(ty, offset, bytes, expr.1);
+        }
+        if ty.is_array() {
+            return Err((CodegenError::InvalidInitializer(ty.clone()), expr.1));
+        }
+        self.record(Element {
+            offset,
+            ty: ty.clone(),
+            expr: expr.clone(),
+        });
+        Ok(())
+    }
+
+    /// Initializes the array of characters `ty` at `offset` from the string `bytes`, which
+    /// only keeps its terminating null if there is room for it
+    fn string(
+        &mut self,
+        ty: &Type,
+        offset: isize,
+        bytes: &[u8],
+        span: Span,
+    ) -> Result<(), Spanned<CodegenError<'src>>> {
+        let Type::Array(elem, len) = ty else {
+            unreachable!("only arrays are initialized from strings")
+        };
+        if bytes.len() > *len {
+            return Err((CodegenError::ExcessInitializers(ty.clone()), span));
+        }
+        for (i, &byte) in bytes.iter().chain([&0]).take(*len).enumerate() {
+            self.record(Element {
+                offset: offset + i as isize,
+                ty: (**elem).clone(),
+                expr: (
+                    Expr::LiteralInteger(byte as u64, Type::Int { signed: true }),
+                    span,
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// The index of the element of `ty` that `designator` picks
+    fn designate(
+        &self,
+        ty: &Type,
+        designator: &Designator<'src>,
+    ) -> Result<usize, Spanned<CodegenError<'src>>> {
+        match (ty.unqualified(), designator) {
+            (Type::Array(_, len), Designator::Index(index)) => match index.eval(self.env)? {
+                i if i >= 0 && (i as u64) < *len as u64 => Ok(i as usize),
+                _ => Err((CodegenError::InvalidDesignator(ty.clone()), index.1)),
+            },
+            (Type::Struct(s), Designator::Member((name, span))) => s
+                .members()
+                .iter()
+                .position(|member| member.name == *name)
+                .ok_or((CodegenError::UnknownMember(name, ty.clone()), *span)),
+            _ => Err((
+                CodegenError::InvalidDesignator(ty.clone()),
+                designator.span(),
+            )),
+        }
+    }
+
+    /// Adds `element`, replacing whatever earlier initializers stored where it is stored
+    fn record(&mut self, element: Element<'src>) {
+        let end = element.offset + element.ty.size();
+        self.elements
+            .retain(|e| e.offset + e.ty.size() <= element.offset || e.offset >= end);
+        self.elements.push(element);
+    }
+}
+
+/// The type of the element of the aggregate `ty` at `index`, and its offset
+fn sub_object(ty: &Type, index: usize) -> Option<(Type, isize)> {
+    match ty.unqualified() {
+        Type::Array(elem, len) if index < *len => {
+            Some(((**elem).clone(), index as isize * elem.size()))
+        }
+        Type::Struct(s) => s
+            .members()
+            .get(index)
+            .map(|member| (member.ty.clone(), member.offset)),
+        _ => None,
+    }
+}
+
+/// The bytes of `expr` if it is a string literal initializing the array of characters `ty`
+fn string<'e>(ty: &Type, expr: &'e Spanned<Expr>) -> Option<&'e [u8]> {
+    match (ty, &expr.0) {
+        (Type::Array(elem, _), Expr::LiteralString(bytes))
+            if matches!(elem.unqualified(), Type::Char { .. }) =>
+        {
+            Some(bytes)
+        }
+        _ => None,
+    }
+}
+
+/// A string initializing an array of characters may be braced, by itself
+fn braced_string<'e>(
+    ty: &Type,
+    items: &'e [(Vec<Designator>, Spanned<Initializer>)],
+) -> Option<&'e [u8]> {
+    match items {
+        [(designators, (Initializer::Expr(expr), _))] if designators.is_empty() => string(ty, expr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(i: u64) -> Spanned<Initializer<'static>> {
+        let expr = (
+            Expr::LiteralInteger(i, Type::Int { signed: true }),
+            Span::new(0, 1),
+        );
+        (Initializer::Expr(expr), Span::new(0, 1))
+    }
+
+    fn list<'src>(
+        items: Vec<(Vec<Designator<'src>>, Spanned<Initializer<'src>>)>,
+    ) -> Spanned<Initializer<'src>> {
+        (Initializer::List(items), Span::new(0, 1))
+    }
+
+    fn index(i: u64) -> Designator<'static> {
+        Designator::Index((
+            Expr::LiteralInteger(i, Type::Int { signed: true }),
+            Span::new(0, 1),
+        ))
+    }
+
+    /// The offset and value of each element, which are all literals
+    fn stores(elements: &[Element]) -> Vec<(isize, u64)> {
+        elements
+            .iter()
+            .map(|element| match element.expr.0 {
+                Expr::LiteralInteger(i, _) => (element.offset, i),
+                _ => panic!("expected a literal"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arrays() {
+        let env = Environment::new();
+        let int = Type::Int { signed: true };
+
+        // The braces around each row may be left out
+        let ty = int.clone().array_of(2).array_of(2);
+        let init = list(vec![
+            (vec![], lit(1)),
+            (vec![], lit(2)),
+            (vec![], list(vec![(vec![], lit(3))])),
+        ]);
+        let elements = lower(&ty, &init, &env).unwrap();
+        assert_eq!(stores(&elements), [(0, 1), (4, 2), (8, 3)]);
+
+        // A designator moves on from where it points, and later ones replace earlier ones
+        let ty = int.clone().array_of(4);
+        let init = list(vec![
+            (vec![], lit(1)),
+            (vec![index(2)], lit(2)),
+            (vec![], lit(3)),
+            (vec![index(0)], lit(4)),
+        ]);
+        let elements = lower(&ty, &init, &env).unwrap();
+        assert_eq!(stores(&elements), [(0, 4), (8, 2), (12, 3)]);
+
+        let init = list(vec![(vec![index(6)], lit(1)), (vec![], lit(2))]);
+        assert_eq!(array_length(&int, &init, &env).unwrap(), Some(8));
+        assert_eq!(array_length(&int, &lit(1), &env).unwrap(), None);
+    }
+
+    #[test]
+    fn excess() {
+        let env = Environment::new();
+        let int = Type::Int { signed: true };
+
+        let init = list(vec![(vec![], lit(1)), (vec![], lit(2))]);
+        assert!(matches!(
+            lower(&int.clone().array_of(1), &init, &env),
+            Err((CodegenError::ExcessInitializers(_), _))
+        ));
+        assert!(matches!(
+            lower(&int, &init, &env),
+            Err((CodegenError::ExcessInitializers(_), _))
+        ));
+
+        let init = list(vec![(vec![index(1)], lit(1))]);
+        assert!(matches!(
+            lower(&int.clone().array_of(1), &init, &env),
+            Err((CodegenError::InvalidDesignator(_), _))
+        ));
+        assert!(matches!(
+            lower(&int, &init, &env),
+            Err((CodegenError::InvalidDesignator(_), _))
+        ));
+    }
+
+    #[test]
+    fn strings() {
+        let env = Environment::new();
+        let char = Type::Char { signed: true };
+        let string = (
+            Initializer::Expr((Expr::LiteralString(b"ab".to_vec()), Span::new(0, 4))),
+            Span::new(0, 4),
+        );
+
+        let elements = lower(&char.clone().array_of(4), &string, &env).unwrap();
+        assert_eq!(stores(&elements), [(0, 97), (1, 98), (2, 0)]);
+        // There is no room for the terminating null
+        let elements = lower(&char.clone().array_of(2), &string, &env).unwrap();
+        assert_eq!(stores(&elements), [(0, 97), (1, 98)]);
+        assert_eq!(array_length(&char, &string, &env).unwrap(), Some(3));
+    }
+}
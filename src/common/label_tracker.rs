@@ -1,7 +1,30 @@
+use super::{Span, Type};
 use std::{collections::HashMap, fmt::Display};
 use strum::EnumCount;
 
-pub struct LabelTracker(HashMap<LabelKind, usize>);
+pub struct LabelTracker {
+    counts: HashMap<LabelKind, usize>,
+    breaks: Vec<String>,
+    continues: Vec<String>,
+    switches: Vec<SwitchCases>,
+}
+
+/// The cases of a switch statement, collected as its body is generated
+#[derive(Debug)]
+pub struct SwitchCases {
+    /// The promoted type of the controlling expression, which case values are converted to
+    pub ty: Type,
+    /// The value of each case, with its label and where the value was written
+    pub cases: Vec<(i64, String, Span)>,
+    /// The label of the default case, and where it was written
+    pub default: Option<(String, Span)>,
+}
+
+impl Default for LabelTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl LabelTracker {
     pub fn new() -> Self {
@@ -14,23 +37,83 @@ impl LabelTracker {
             (LabelKind::TernaryEnd, 0),
             (LabelKind::IfElse, 0),
             (LabelKind::IfEnd, 0),
+            (LabelKind::LoopStart, 0),
+            (LabelKind::LoopContinue, 0),
+            (LabelKind::LoopEnd, 0),
+            (LabelKind::String, 0),
+            (LabelKind::CastLarge, 0),
+            (LabelKind::CastEnd, 0),
+            (LabelKind::Static, 0),
+            (LabelKind::SwitchCase, 0),
+            (LabelKind::SwitchDispatch, 0),
+            (LabelKind::SwitchEnd, 0),
+            (LabelKind::SwitchSearch, 0),
+            (LabelKind::SwitchTable, 0),
+            (LabelKind::VaArgStack, 0),
+            (LabelKind::VaArgEnd, 0),
         ]);
         assert_eq!(hm.len(), LabelKind::COUNT);
-        Self(hm)
+        Self {
+            counts: hm,
+            breaks: vec![],
+            continues: vec![],
+            switches: vec![],
+        }
     }
 
     pub fn create(&mut self, kind: LabelKind) -> String {
         let s = format!("{}_{}", kind, self.index(kind));
         self.increment(kind);
-        return s;
+        s
+    }
+
+    /// Enters a loop whose `break` and `continue` jump to the given labels
+    pub fn push_loop(&mut self, r#continue: String, r#break: String) {
+        self.continues.push(r#continue);
+        self.breaks.push(r#break);
+    }
+
+    pub fn pop_loop(&mut self) {
+        self.continues.pop();
+        self.breaks.pop();
+    }
+
+    /// Enters a switch on a value of type `ty`, whose `break` jumps to the given label.
+    /// `continue` still refers to the enclosing loop.
+    pub fn push_switch(&mut self, r#break: String, ty: Type) {
+        self.breaks.push(r#break);
+        self.switches.push(SwitchCases {
+            ty,
+            cases: vec![],
+            default: None,
+        });
+    }
+
+    /// Leaves the innermost switch, returning its cases
+    pub fn pop_switch(&mut self) -> Option<SwitchCases> {
+        self.breaks.pop();
+        self.switches.pop()
+    }
+
+    /// The innermost switch, which `case` and `default` labels belong to
+    pub fn switch_mut(&mut self) -> Option<&mut SwitchCases> {
+        self.switches.last_mut()
+    }
+
+    pub fn break_target(&self) -> Option<&str> {
+        self.breaks.last().map(String::as_str)
+    }
+
+    pub fn continue_target(&self) -> Option<&str> {
+        self.continues.last().map(String::as_str)
     }
 
     fn index(&self, kind: LabelKind) -> usize {
-        self.0.get(&kind).copied().expect("infallible")
+        self.counts.get(&kind).copied().expect("infallible")
     }
 
     fn increment(&mut self, kind: LabelKind) {
-        let i = self.0.get_mut(&kind).expect("infallible");
+        let i = self.counts.get_mut(&kind).expect("infallible");
         *i += 1;
     }
 }
@@ -45,8 +128,24 @@ pub enum LabelKind {
     TernaryEnd,
     IfElse,
     IfEnd,
+    LoopStart,
+    LoopContinue,
+    LoopEnd,
+    String,
+    CastLarge,
+    CastEnd,
+    Static,
+    SwitchCase,
+    SwitchDispatch,
+    SwitchEnd,
+    SwitchSearch,
+    SwitchTable,
+    VaArgStack,
+    VaArgEnd,
 }
 
+// Data in `.rodata`, and the labels of a switch which its jump tables refer to, are labelled
+// with assembler local names, which no C identifier can clash with
 impl Display for LabelKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -58,6 +157,20 @@ impl Display for LabelKind {
             LabelKind::TernaryEnd => write!(f, "cond_end"),
             LabelKind::IfElse => write!(f, "if_else"),
             LabelKind::IfEnd => write!(f, "if_end"),
+            LabelKind::LoopStart => write!(f, "loop_start"),
+            LabelKind::LoopContinue => write!(f, "loop_continue"),
+            LabelKind::LoopEnd => write!(f, "loop_end"),
+            LabelKind::String => write!(f, ".Lstr"),
+            LabelKind::CastLarge => write!(f, "cast_large"),
+            LabelKind::CastEnd => write!(f, "cast_end"),
+            LabelKind::Static => write!(f, "static"),
+            LabelKind::SwitchCase => write!(f, ".Lcase"),
+            LabelKind::SwitchDispatch => write!(f, ".Lswitch"),
+            LabelKind::SwitchEnd => write!(f, ".Lswitch_end"),
+            LabelKind::SwitchSearch => write!(f, ".Lswitch_search"),
+            LabelKind::SwitchTable => write!(f, ".Lswitch_table"),
+            LabelKind::VaArgStack => write!(f, "va_arg_stack"),
+            LabelKind::VaArgEnd => write!(f, "va_arg_end"),
         }
     }
 }
@@ -85,6 +198,34 @@ mod tests {
         assert_eq!(label, "if_else_0");
         let label = tracker.create(LabelKind::IfEnd);
         assert_eq!(label, "if_end_0");
+        let label = tracker.create(LabelKind::LoopStart);
+        assert_eq!(label, "loop_start_0");
+        let label = tracker.create(LabelKind::LoopContinue);
+        assert_eq!(label, "loop_continue_0");
+        let label = tracker.create(LabelKind::LoopEnd);
+        assert_eq!(label, "loop_end_0");
+        let label = tracker.create(LabelKind::String);
+        assert_eq!(label, ".Lstr_0");
+        let label = tracker.create(LabelKind::CastLarge);
+        assert_eq!(label, "cast_large_0");
+        let label = tracker.create(LabelKind::CastEnd);
+        assert_eq!(label, "cast_end_0");
+        let label = tracker.create(LabelKind::Static);
+        assert_eq!(label, "static_0");
+        let label = tracker.create(LabelKind::SwitchCase);
+        assert_eq!(label, ".Lcase_0");
+        let label = tracker.create(LabelKind::SwitchDispatch);
+        assert_eq!(label, ".Lswitch_0");
+        let label = tracker.create(LabelKind::SwitchEnd);
+        assert_eq!(label, ".Lswitch_end_0");
+        let label = tracker.create(LabelKind::SwitchSearch);
+        assert_eq!(label, ".Lswitch_search_0");
+        let label = tracker.create(LabelKind::SwitchTable);
+        assert_eq!(label, ".Lswitch_table_0");
+        let label = tracker.create(LabelKind::VaArgStack);
+        assert_eq!(label, "va_arg_stack_0");
+        let label = tracker.create(LabelKind::VaArgEnd);
+        assert_eq!(label, "va_arg_end_0");
     }
 
     #[test]
@@ -98,6 +239,20 @@ mod tests {
         assert_eq!(tracker.index(LabelKind::TernaryEnd), 0);
         assert_eq!(tracker.index(LabelKind::IfElse), 0);
         assert_eq!(tracker.index(LabelKind::IfEnd), 0);
+        assert_eq!(tracker.index(LabelKind::LoopStart), 0);
+        assert_eq!(tracker.index(LabelKind::LoopContinue), 0);
+        assert_eq!(tracker.index(LabelKind::LoopEnd), 0);
+        assert_eq!(tracker.index(LabelKind::String), 0);
+        assert_eq!(tracker.index(LabelKind::CastLarge), 0);
+        assert_eq!(tracker.index(LabelKind::CastEnd), 0);
+        assert_eq!(tracker.index(LabelKind::Static), 0);
+        assert_eq!(tracker.index(LabelKind::SwitchCase), 0);
+        assert_eq!(tracker.index(LabelKind::SwitchDispatch), 0);
+        assert_eq!(tracker.index(LabelKind::SwitchEnd), 0);
+        assert_eq!(tracker.index(LabelKind::SwitchSearch), 0);
+        assert_eq!(tracker.index(LabelKind::SwitchTable), 0);
+        assert_eq!(tracker.index(LabelKind::VaArgStack), 0);
+        assert_eq!(tracker.index(LabelKind::VaArgEnd), 0);
     }
 
     #[test]
@@ -119,5 +274,75 @@ mod tests {
         assert_eq!(tracker.index(LabelKind::IfElse), 1);
         tracker.increment(LabelKind::IfEnd);
         assert_eq!(tracker.index(LabelKind::IfEnd), 1);
+        tracker.increment(LabelKind::LoopStart);
+        assert_eq!(tracker.index(LabelKind::LoopStart), 1);
+        tracker.increment(LabelKind::LoopContinue);
+        assert_eq!(tracker.index(LabelKind::LoopContinue), 1);
+        tracker.increment(LabelKind::LoopEnd);
+        assert_eq!(tracker.index(LabelKind::LoopEnd), 1);
+        tracker.increment(LabelKind::String);
+        assert_eq!(tracker.index(LabelKind::String), 1);
+        tracker.increment(LabelKind::CastLarge);
+        assert_eq!(tracker.index(LabelKind::CastLarge), 1);
+        tracker.increment(LabelKind::CastEnd);
+        assert_eq!(tracker.index(LabelKind::CastEnd), 1);
+        tracker.increment(LabelKind::Static);
+        assert_eq!(tracker.index(LabelKind::Static), 1);
+        tracker.increment(LabelKind::SwitchCase);
+        assert_eq!(tracker.index(LabelKind::SwitchCase), 1);
+        tracker.increment(LabelKind::SwitchDispatch);
+        assert_eq!(tracker.index(LabelKind::SwitchDispatch), 1);
+        tracker.increment(LabelKind::SwitchEnd);
+        assert_eq!(tracker.index(LabelKind::SwitchEnd), 1);
+        tracker.increment(LabelKind::SwitchSearch);
+        assert_eq!(tracker.index(LabelKind::SwitchSearch), 1);
+        tracker.increment(LabelKind::SwitchTable);
+        assert_eq!(tracker.index(LabelKind::SwitchTable), 1);
+        tracker.increment(LabelKind::VaArgStack);
+        assert_eq!(tracker.index(LabelKind::VaArgStack), 1);
+        tracker.increment(LabelKind::VaArgEnd);
+        assert_eq!(tracker.index(LabelKind::VaArgEnd), 1);
+    }
+
+    #[test]
+    fn loop_targets() {
+        let mut tracker = LabelTracker::new();
+        assert_eq!(tracker.break_target(), None);
+        assert_eq!(tracker.continue_target(), None);
+
+        tracker.push_loop("outer_cont".into(), "outer_end".into());
+        tracker.push_loop("inner_cont".into(), "inner_end".into());
+        assert_eq!(tracker.break_target(), Some("inner_end"));
+        assert_eq!(tracker.continue_target(), Some("inner_cont"));
+
+        tracker.pop_loop();
+        assert_eq!(tracker.break_target(), Some("outer_end"));
+        assert_eq!(tracker.continue_target(), Some("outer_cont"));
+
+        tracker.pop_loop();
+        assert_eq!(tracker.break_target(), None);
+    }
+
+    #[test]
+    fn data_labels_are_local() {
+        let mut tracker = LabelTracker::new();
+        assert!(tracker.create(LabelKind::String).starts_with(".L"));
+        assert!(tracker.create(LabelKind::SwitchTable).starts_with(".L"));
+        assert!(tracker.create(LabelKind::SwitchCase).starts_with(".L"));
+        assert!(tracker.create(LabelKind::SwitchEnd).starts_with(".L"));
+    }
+
+    #[test]
+    fn switch_targets() {
+        let mut tracker = LabelTracker::new();
+        tracker.push_loop("loop_cont".into(), "loop_end".into());
+        tracker.push_switch("switch_end".into(), Type::Int { signed: true });
+        assert_eq!(tracker.break_target(), Some("switch_end"));
+        assert_eq!(tracker.continue_target(), Some("loop_cont"));
+        assert!(tracker.switch_mut().is_some());
+
+        assert!(tracker.pop_switch().is_some());
+        assert_eq!(tracker.break_target(), Some("loop_end"));
+        assert!(tracker.switch_mut().is_none());
     }
 }
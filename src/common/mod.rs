@@ -1,18 +1,24 @@
+pub mod abi;
 pub mod codegen;
+pub mod const_eval;
 pub mod decl;
 pub mod desugar;
 pub mod emitter;
 pub mod env;
 pub mod expr;
+pub mod init;
 pub mod label_tracker;
 pub mod op;
 pub mod span_ty;
 pub mod stmt;
 pub mod token;
+pub mod ty;
+pub mod type_spec;
 
 // Re-Exports
 pub use codegen::{Codegen, CodegenError};
-pub use decl::FnDeclaration;
+pub use const_eval::ConstEval;
+pub use decl::{Decl, Declarator, FnDeclaration, StorageClass};
 pub use desugar::Desugar;
 pub use env::Environment;
 pub use expr::Expr;
@@ -20,3 +26,5 @@ pub use op::{BinaryOperator, UnaryOperator};
 pub use span_ty::{Span, Spanned};
 pub use stmt::Stmt;
 pub use token::Token;
+pub use ty::{Type, TypeOf};
+pub use type_spec::TypeSpec;
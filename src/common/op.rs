@@ -6,6 +6,12 @@ pub enum UnaryOperator {
     Minus,
     LogicalNot,
     BitwiseNot,
+    Deref,
+    AddressOf,
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement,
 }
 
 impl TryFrom<Token<'_>> for UnaryOperator {
@@ -17,10 +23,25 @@ impl TryFrom<Token<'_>> for UnaryOperator {
             Token::Minus => Self::Minus,
             Token::Exclamation => Self::LogicalNot,
             Token::Tilde => Self::BitwiseNot,
+            Token::Star => Self::Deref,
+            Token::And => Self::AddressOf,
+            // The postfix forms are told apart by the parser
+            Token::PlusPlus => Self::PreIncrement,
+            Token::MinusMinus => Self::PreDecrement,
             _ => return Err(()),
         })
     }
 }
+impl UnaryOperator {
+    /// Whether this is a form of `++` or `--`, which update their operand
+    pub fn is_increment(&self) -> bool {
+        matches!(
+            self,
+            Self::PreIncrement | Self::PreDecrement | Self::PostIncrement | Self::PostDecrement
+        )
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BinaryOperator {
     // Math
@@ -61,6 +82,9 @@ pub enum BinaryOperator {
     XorEquals,
     LeftShiftEquals,
     RightShiftEquals,
+
+    // Sequencing
+    Comma,
 }
 
 impl TryFrom<Token<'_>> for BinaryOperator {
@@ -96,6 +120,7 @@ impl TryFrom<Token<'_>> for BinaryOperator {
             Token::CaretEquals => Self::XorEquals,
             Token::LeftShiftEquals => Self::LeftShiftEquals,
             Token::RightShiftEquals => Self::RightShiftEquals,
+            Token::Comma => Self::Comma,
             _ => return Err(()),
         })
     }
@@ -139,6 +164,21 @@ impl BinaryOperator {
 mod tests {
     use super::*;
 
+    #[test]
+    fn is_increment() {
+        assert!(UnaryOperator::PreIncrement.is_increment());
+        assert!(UnaryOperator::PreDecrement.is_increment());
+        assert!(UnaryOperator::PostIncrement.is_increment());
+        assert!(UnaryOperator::PostDecrement.is_increment());
+
+        assert!(!UnaryOperator::Plus.is_increment());
+        assert!(!UnaryOperator::Minus.is_increment());
+        assert!(!UnaryOperator::LogicalNot.is_increment());
+        assert!(!UnaryOperator::BitwiseNot.is_increment());
+        assert!(!UnaryOperator::Deref.is_increment());
+        assert!(!UnaryOperator::AddressOf.is_increment());
+    }
+
     #[test]
     fn is_compound_assignment() {
         assert!(BinaryOperator::PlusEquals.is_compound_assignment());
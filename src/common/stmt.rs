@@ -1,7 +1,18 @@
 use super::{
-    emitter::Emitter, env::Environment, label_tracker::LabelTracker, Codegen, CodegenError,
+    abi::{
+        assign_registers, classify, emit_load_eightbyte, ArgClass, RET_REGISTERS, SSE_RET_REGISTERS,
+    },
+    codegen::{emit_cast, emit_cmp_zero, emit_copy, emit_store_scalar},
+    decl::{static_data, Declarator, StorageClass},
+    emitter::Emitter,
+    env::{Environment, Symbol},
+    expr::converts,
+    init::{lower, Element, Initializer},
+    label_tracker::{LabelTracker, SwitchCases},
+    ty::condition_ty,
+    Codegen, CodegenError, ConstEval, TypeOf,
 };
-use super::{Expr, Spanned};
+use super::{Expr, Span, Spanned, Type, TypeSpec};
 use crate::common::label_tracker::LabelKind;
 use clap::error::Result;
 
@@ -9,13 +20,32 @@ use clap::error::Result;
 pub enum Stmt<'src> {
     Block(Vec<Spanned<Self>>),
     Expression(Spanned<Expr<'src>>),
-    Declare(Spanned<&'src str>, Option<Spanned<Expr<'src>>>),
+    Declare(Option<StorageClass>, TypeSpec<'src>, Vec<Declarator<'src>>),
+    DeclareType(TypeSpec<'src>),
+    Typedef(TypeSpec<'src>, Spanned<&'src str>),
     If(
         Spanned<Expr<'src>>,
         Box<Spanned<Self>>,
         Option<Box<Spanned<Self>>>,
     ),
-    Return(Spanned<Expr<'src>>),
+    While(Spanned<Expr<'src>>, Box<Spanned<Self>>),
+    DoWhile(Box<Spanned<Self>>, Spanned<Expr<'src>>),
+    For(
+        Box<Spanned<Self>>,
+        Option<Spanned<Expr<'src>>>,
+        Option<Spanned<Expr<'src>>>,
+        Box<Spanned<Self>>,
+    ),
+    Switch(Spanned<Expr<'src>>, Box<Spanned<Self>>),
+    /// The value must be an integer constant expression
+    Case(Spanned<Expr<'src>>, Box<Spanned<Self>>),
+    Default(Box<Spanned<Self>>),
+    Label(Spanned<&'src str>, Box<Spanned<Self>>),
+    Goto(Spanned<&'src str>),
+    Break,
+    Continue,
+    /// The value is left out by functions returning `void`
+    Return(Option<Spanned<Expr<'src>>>),
     Empty,
 }
 
@@ -44,25 +74,38 @@ impl<'src> Codegen<'src> for Spanned<Stmt<'src>> {
             (Stmt::Block(stmts), _) => {
                 env.new_scope();
                 stmts.code_gen(lt, em, env)?;
-                let deallocate = env.end_scope().expect("infallible");
-                em.emit_instr(&format!("add ${}, %rsp", deallocate));
+                env.end_scope();
             }
 
             (Stmt::Expression(expr), _) => expr.code_gen(lt, em, env)?,
 
-            (Stmt::Declare((name, name_span), expr), _) => {
-                if env.contains(name) {
-                    let (_, init_span) = env.get(name).expect("infallible");
-                    return Err((CodegenError::RedeclaredVariable(name, init_span), name_span));
+            (Stmt::Declare(storage, ty, declarators), span) => {
+                let base = ty.resolve(env, span)?;
+                for declarator in declarators {
+                    let ty = declarator.resolve(&base, env)?;
+                    declare_local(lt, em, env, storage, ty, declarator.name, declarator.init)?;
                 }
+            }
 
-                match expr {
-                    Some(expr) => expr.code_gen(lt, em, env)?,
-                    None => em.emit_instr("mov $0, %rax"),
-                }
-                em.emit_instr("push %rax");
+            (Stmt::DeclareType(ty), span) => {
+                ty.resolve(env, span)?;
+            }
 
-                env.put(name, name_span);
+            (Stmt::Typedef(ty, (name, name_span)), _) => {
+                let ty = ty.resolve(env, name_span)?;
+                match env.get_in_scope(name) {
+                    // Repeating a typedef for the same type is allowed
+                    Some(Symbol::Typedef(prev, _)) if prev == ty => {}
+                    Some(symbol) => {
+                        return Err((
+                            CodegenError::RedeclaredVariable(name, symbol.span()),
+                            name_span,
+                        ))
+                    }
+                    None => {
+                        env.put_typedef(name, ty, name_span);
+                    }
+                }
             }
 
             (Stmt::If(condition, then, r#else), _) => {
@@ -70,8 +113,9 @@ impl<'src> Codegen<'src> for Spanned<Stmt<'src>> {
                 let end = &lt.create(LabelKind::TernaryEnd);
                 let else_exists = r#else.is_some();
 
+                let cond_ty = condition_ty(&condition, env)?;
                 condition.code_gen(lt, em, env)?;
-                em.emit_instr("cmp $0, %rax");
+                emit_cmp_zero(em, &cond_ty);
                 em.emit_instr(&format!("je {}", if else_exists { els } else { end }));
                 then.code_gen(lt, em, env)?;
                 em.emit_instr(&format!("jmp {}", end));
@@ -82,8 +126,156 @@ impl<'src> Codegen<'src> for Spanned<Stmt<'src>> {
                 em.emit_label(end);
             }
 
-            (Stmt::Return(expr), _) => {
-                expr.code_gen(lt, em, env)?;
+            (Stmt::While(condition, body), _) => {
+                let start = lt.create(LabelKind::LoopStart);
+                let end = lt.create(LabelKind::LoopEnd);
+
+                em.emit_label(&start);
+                let cond_ty = condition_ty(&condition, env)?;
+                condition.code_gen(lt, em, env)?;
+                emit_cmp_zero(em, &cond_ty);
+                em.emit_instr(&format!("je {}", end));
+                lt.push_loop(start.clone(), end.clone());
+                body.code_gen(lt, em, env)?;
+                lt.pop_loop();
+                em.emit_instr(&format!("jmp {}", start));
+                em.emit_label(&end);
+            }
+
+            (Stmt::DoWhile(body, condition), _) => {
+                let start = lt.create(LabelKind::LoopStart);
+                let cont = lt.create(LabelKind::LoopContinue);
+                let end = lt.create(LabelKind::LoopEnd);
+
+                em.emit_label(&start);
+                lt.push_loop(cont.clone(), end.clone());
+                body.code_gen(lt, em, env)?;
+                lt.pop_loop();
+                em.emit_label(&cont);
+                let cond_ty = condition_ty(&condition, env)?;
+                condition.code_gen(lt, em, env)?;
+                emit_cmp_zero(em, &cond_ty);
+                em.emit_instr(&format!("jne {}", start));
+                em.emit_label(&end);
+            }
+
+            (Stmt::For(init, condition, step, body), _) => {
+                let start = lt.create(LabelKind::LoopStart);
+                let cont = lt.create(LabelKind::LoopContinue);
+                let end = lt.create(LabelKind::LoopEnd);
+
+                // A declaration in the init clause is scoped to the loop
+                env.new_scope();
+                init.code_gen(lt, em, env)?;
+                em.emit_label(&start);
+                if let Some(condition) = condition {
+                    let cond_ty = condition_ty(&condition, env)?;
+                    condition.code_gen(lt, em, env)?;
+                    emit_cmp_zero(em, &cond_ty);
+                    em.emit_instr(&format!("je {}", end));
+                }
+                lt.push_loop(cont.clone(), end.clone());
+                body.code_gen(lt, em, env)?;
+                lt.pop_loop();
+                em.emit_label(&cont);
+                if let Some(step) = step {
+                    step.code_gen(lt, em, env)?;
+                }
+                em.emit_instr(&format!("jmp {}", start));
+                em.emit_label(&end);
+                env.end_scope();
+            }
+
+            (Stmt::Switch(condition, body), _) => {
+                let ty = condition.ty(env)?;
+                if !ty.is_integer() {
+                    return Err((CodegenError::InvalidSwitch(ty), condition.1));
+                }
+                let dispatch = lt.create(LabelKind::SwitchDispatch);
+                let end = lt.create(LabelKind::SwitchEnd);
+
+                // The body comes first so every case is known once the dispatch is generated.
+                // Promotion only widens, so the value in %rax is already the promoted one.
+                condition.code_gen(lt, em, env)?;
+                em.emit_instr(&format!("jmp {}", dispatch));
+                lt.push_switch(end.clone(), ty.promote());
+                body.code_gen(lt, em, env)?;
+                let cases = lt.pop_switch().expect("infallible");
+                em.emit_instr(&format!("jmp {}", end));
+                em.emit_label(&dispatch);
+                emit_dispatch(em, lt, cases, &end);
+                em.emit_label(&end);
+            }
+
+            (Stmt::Case(value, body), span) => {
+                let label = lt.create(LabelKind::SwitchCase);
+                let switch = lt.switch_mut().ok_or((
+                    CodegenError::CaseOutsideSwitch,
+                    Span::new(span.start, value.1.end),
+                ))?;
+                let n = switch.ty.truncate(value.eval(env)?);
+                if let Some((_, _, init_span)) = switch.cases.iter().find(|(case, ..)| *case == n) {
+                    return Err((CodegenError::DuplicateCase(n, *init_span), value.1));
+                }
+                switch.cases.push((n, label.clone(), value.1));
+
+                em.emit_label(&label);
+                body.code_gen(lt, em, env)?;
+            }
+
+            (Stmt::Default(body), span) => {
+                let label = lt.create(LabelKind::SwitchCase);
+                let default_span = Span::new(span.start, body.1.start);
+                let switch = lt
+                    .switch_mut()
+                    .ok_or((CodegenError::CaseOutsideSwitch, default_span))?;
+                if let Some((_, init_span)) = switch.default {
+                    return Err((CodegenError::DuplicateDefault(init_span), default_span));
+                }
+                switch.default = Some((label.clone(), default_span));
+
+                em.emit_label(&label);
+                body.code_gen(lt, em, env)?;
+            }
+
+            (Stmt::Label((name, name_span), body), _) => {
+                if !env.put_label(name, name_span) {
+                    let init_span = env.get_label(name).expect("infallible");
+                    return Err((CodegenError::DuplicateLabel(name, init_span), name_span));
+                }
+                em.emit_label(&env.label_symbol(name));
+                body.code_gen(lt, em, env)?;
+            }
+
+            (Stmt::Goto((name, name_span)), _) => {
+                env.use_label(name, name_span);
+                em.emit_instr(&format!("jmp {}", env.label_symbol(name)));
+            }
+
+            (Stmt::Break, span) => {
+                let label = lt
+                    .break_target()
+                    .ok_or((CodegenError::BreakOutsideLoop, span))?;
+                em.emit_instr(&format!("jmp {}", label));
+            }
+
+            (Stmt::Continue, span) => {
+                let label = lt
+                    .continue_target()
+                    .ok_or((CodegenError::ContinueOutsideLoop, span))?;
+                em.emit_instr(&format!("jmp {}", label));
+            }
+
+            (Stmt::Return(expr), span) => {
+                let ret = env.ret.clone().expect("return outside of a function");
+                match expr {
+                    Some(expr) => emit_return_value(lt, em, env, &ret, expr)?,
+                    // Only a function returning `void` may leave out the value
+                    None if !ret.is_void() => {
+                        return Err((CodegenError::IncompatibleTypes(ret, Type::Void), span))
+                    }
+                    None => {}
+                }
                 em.emit_instr("mov %rbp, %rsp");
                 em.emit_instr("pop %rbp");
                 em.emit_instr("ret");
@@ -94,3 +286,255 @@ impl<'src> Codegen<'src> for Spanned<Stmt<'src>> {
         Ok(())
     }
 }
+
+/// Moves the value of `expr` to where the caller of a function returning `ret` expects it
+fn emit_return_value<'src>(
+    lt: &mut LabelTracker,
+    em: &mut Emitter,
+    env: &mut Environment<'src>,
+    ret: &Type,
+    expr: Spanned<Expr<'src>>,
+) -> Result<(), Spanned<CodegenError<'src>>> {
+    let ty = expr.ty(env)?.decay();
+    if !converts(ret, &ty, &expr, env) {
+        return Err((CodegenError::IncompatibleTypes(ret.clone(), ty), expr.1));
+    }
+
+    expr.code_gen(lt, em, env)?;
+    if ret.is_float() {
+        emit_cast(em, lt, &ty, ret);
+        em.emit_instr("movq %rax, %xmm0");
+    } else if !ret.is_aggregate() {
+        emit_cast(em, lt, &ty, ret);
+    } else {
+        match classify(ret)[..] {
+            // The caller passed the address to copy the struct to, which is also handed back
+            // in %rax
+            [ArgClass::Memory] => {
+                let ret_ptr = env.ret_ptr.expect("infallible");
+                em.emit_instr(&format!("mov {}(%rbp), %rcx", ret_ptr));
+                emit_copy(em, ret.size());
+                em.emit_instr("mov %rcx, %rax");
+            }
+            ref classes => {
+                let regs = assign_registers(
+                    classes,
+                    (&RET_REGISTERS, &mut 0),
+                    (&SSE_RET_REGISTERS, &mut 0),
+                )
+                .expect("infallible");
+                em.emit_instr("mov %rax, %rcx");
+                for (i, reg) in regs.into_iter().enumerate() {
+                    emit_load_eightbyte(em, "%rcx", i as isize * 8, ret.size(), reg);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Declares the local variable `name`, which has automatic storage unless `storage` says
+/// otherwise
+fn declare_local<'src>(
+    lt: &mut LabelTracker,
+    em: &mut Emitter,
+    env: &mut Environment<'src>,
+    storage: Option<StorageClass>,
+    ty: Type,
+    (name, name_span): Spanned<&'src str>,
+    init: Option<Spanned<Initializer<'src>>>,
+) -> Result<(), Spanned<CodegenError<'src>>> {
+    if let Some(symbol) = env.get_in_scope(name) {
+        return Err((
+            CodegenError::RedeclaredVariable(name, symbol.span()),
+            name_span,
+        ));
+    }
+
+    if !ty.is_complete() && storage != Some(StorageClass::Extern) {
+        return Err((CodegenError::IncompleteType(ty), name_span));
+    }
+
+    match storage {
+        // Lives for the whole program under a label of its own, so it keeps its value across
+        // calls. The initializer is applied once, before the program starts.
+        Some(StorageClass::Static) => {
+            let label = format!("{}.{}", name, lt.create(LabelKind::Static));
+            env.put_global(name, label.clone(), ty.clone(), name_span);
+            match init {
+                Some(init) => {
+                    let values = static_data(lt, em, env, &ty, &init)?;
+                    em.emit_data(&label, ty.align(), &values, false);
+                }
+                None => em.emit_bss(&label, ty.size(), ty.align(), false),
+            }
+            return Ok(());
+        }
+        // Refers to a variable defined at file scope, possibly in another translation unit
+        Some(StorageClass::Extern) => {
+            if let Some((_, span)) = init {
+                return Err((CodegenError::InvalidInitializer(ty), span));
+            }
+            env.put_global(name, name.to_owned(), ty, name_span);
+            return Ok(());
+        }
+        None => {}
+    }
+
+    env.put(name, ty.clone(), name_span);
+    let offset = env.sp;
+
+    let Some(init) = init else {
+        if !ty.is_aggregate() {
+            em.emit_instr("mov $0, %rax");
+            emit_store_scalar(em, &ty, &format!("{}(%rbp)", offset));
+        }
+        return Ok(());
+    };
+
+    // Whatever the initializer stores nothing into is zero
+    let elements = lower(&ty, &init, env)?;
+    if !matches!(&elements[..], [element] if element.ty == ty) {
+        emit_zero(em, offset, ty.size());
+    }
+
+    for Element {
+        offset: elem_offset,
+        ty,
+        expr,
+    } in elements
+    {
+        let dst = offset + elem_offset;
+        let init_ty = expr.ty(env)?;
+        if ty.is_aggregate() {
+            if init_ty != *ty.unqualified() {
+                return Err((CodegenError::IncompatibleTypes(ty, init_ty), expr.1));
+            }
+            expr.code_gen(lt, em, env)?;
+            em.emit_instr(&format!("lea {}(%rbp), %rcx", dst));
+            emit_copy(em, ty.size());
+        } else {
+            let init_ty = init_ty.decay();
+            if !converts(&ty, &init_ty, &expr, env) {
+                return Err((CodegenError::IncompatibleTypes(ty, init_ty), expr.1));
+            }
+            expr.code_gen(lt, em, env)?;
+            emit_cast(em, lt, &init_ty, &ty);
+            emit_store_scalar(em, &ty, &format!("{}(%rbp)", dst));
+        }
+    }
+    Ok(())
+}
+
+/// Zeroes the `size` bytes of the frame starting at `offset`
+fn emit_zero(em: &mut Emitter, offset: isize, size: isize) {
+    em.emit_instr(&format!("lea {}(%rbp), %rdi", offset));
+    em.emit_instr(&format!("mov ${}, %rcx", size));
+    em.emit_instr("xor %eax, %eax");
+    em.emit_instr("rep stosb");
+}
+
+/// Jumps from the value in %rax to the matching case of a switch, or to its default. Dense
+/// cases are looked up in a jump table, sparse ones found with a binary search.
+fn emit_dispatch(em: &mut Emitter, lt: &mut LabelTracker, switch: SwitchCases, end: &str) {
+    let default = switch.default.map_or(end.to_owned(), |(label, _)| label);
+    let signed = switch.ty.is_signed();
+    let mut cases: Vec<(i64, String)> = switch
+        .cases
+        .into_iter()
+        .map(|(n, label, _)| (n, label))
+        .collect();
+    if signed {
+        cases.sort_by_key(|(n, _)| *n);
+    } else {
+        cases.sort_by_key(|(n, _)| *n as u64);
+    }
+
+    match (cases.first(), cases.last()) {
+        (Some((min, _)), Some((max, _))) if cases.len() >= JUMP_TABLE_MIN_CASES => {
+            // Wrapping keeps this right for unsigned values above i64::MAX
+            let range = max.wrapping_sub(*min) as u64;
+            if range < 3 * cases.len() as u64 {
+                emit_jump_table(em, lt, &cases, range, &default);
+            } else {
+                emit_search(em, lt, &cases, signed, &default);
+            }
+        }
+        _ => emit_search(em, lt, &cases, signed, &default),
+    }
+}
+
+/// Fewer cases than this are always compared one by one
+const JUMP_TABLE_MIN_CASES: usize = 4;
+
+/// Indexes a table of the offsets of every case from the smallest to the largest, where
+/// missing values go to the default
+fn emit_jump_table(
+    em: &mut Emitter,
+    lt: &mut LabelTracker,
+    cases: &[(i64, String)],
+    range: u64,
+    default: &str,
+) {
+    let table = lt.create(LabelKind::SwitchTable);
+    let min = cases[0].0;
+    let mut targets = vec![default.to_owned(); range as usize + 1];
+    for (n, label) in cases {
+        targets[n.wrapping_sub(min) as u64 as usize] = label.clone();
+    }
+    em.emit_jump_table(&table, &targets);
+
+    // Values below the smallest case wrap around to be above the largest
+    match min {
+        0 => {}
+        min if i32::try_from(min).is_ok() => em.emit_instr(&format!("sub ${}, %rax", min)),
+        min => {
+            em.emit_instr(&format!("movabs ${}, %rcx", min));
+            em.emit_instr("sub %rcx, %rax");
+        }
+    }
+    em.emit_instr(&format!("cmp ${}, %rax", range));
+    em.emit_instr(&format!("ja {}", default));
+    em.emit_instr(&format!("lea {}(%rip), %rcx", table));
+    em.emit_instr("movslq (%rcx,%rax,4), %rax");
+    em.emit_instr("add %rcx, %rax");
+    em.emit_instr("jmp *%rax");
+}
+
+/// Halves the sorted cases until few enough are left to compare one by one
+fn emit_search(
+    em: &mut Emitter,
+    lt: &mut LabelTracker,
+    cases: &[(i64, String)],
+    signed: bool,
+    default: &str,
+) {
+    if cases.len() < JUMP_TABLE_MIN_CASES {
+        for (n, label) in cases {
+            emit_cmp_case(em, *n);
+            em.emit_instr(&format!("je {}", label));
+        }
+        em.emit_instr(&format!("jmp {}", default));
+        return;
+    }
+
+    let mid = cases.len() / 2;
+    let (n, label) = &cases[mid];
+    let lower = lt.create(LabelKind::SwitchSearch);
+    emit_cmp_case(em, *n);
+    em.emit_instr(&format!("je {}", label));
+    em.emit_instr(&format!("{} {}", if signed { "jl" } else { "jb" }, lower));
+    emit_search(em, lt, &cases[mid + 1..], signed, default);
+    em.emit_label(&lower);
+    emit_search(em, lt, &cases[..mid], signed, default);
+}
+
+/// Compares %rax with a case value
+fn emit_cmp_case(em: &mut Emitter, n: i64) {
+    if i32::try_from(n).is_ok() {
+        em.emit_instr(&format!("cmp ${}, %rax", n));
+    } else {
+        em.emit_instr(&format!("movabs ${}, %rcx", n));
+        em.emit_instr("cmp %rcx, %rax");
+    }
+}
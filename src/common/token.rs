@@ -1,15 +1,52 @@
+use super::Type;
 use std::fmt::Display;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Token<'src> {
     /* Keywords */
     Int,
+    Char,
+    Short,
+    Long,
+    Float,
+    Double,
+    Void,
+    Signed,
+    Unsigned,
     Return,
     If,
     Else,
+    While,
+    Do,
+    For,
+    Break,
+    Continue,
+    Sizeof,
+    Alignof,
+    Struct,
+    Union,
+    Enum,
+    Typedef,
+    Static,
+    Extern,
+    Const,
+    Volatile,
+    Switch,
+    Case,
+    Default,
+    Goto,
+    // Variable arguments, which are builtin as there are no headers to declare them
+    VaList,
+    VaStart,
+    VaArg,
+    VaEnd,
 
     /* Literals */
-    LitInteger(u64),
+    LitInteger(u64, IntegerType),
+    /// The bits of the value as an `f64`, and whether an `f` suffix makes it a `float`
+    LitFloat(u64, bool),
+    LitChar(u8),
+    LitString(&'src str),
 
     /* Miscellaneous */
     Identifier(&'src str),
@@ -20,6 +57,16 @@ pub enum Token<'src> {
     CloseParen,
     OpenBrace,
     CloseBrace,
+    OpenBracket,
+    CloseBracket,
+
+    // Member Access Operators
+    Dot,
+    Arrow,
+
+    // Increment and Decrement Operators
+    PlusPlus,
+    MinusMinus,
 
     // Math Operators
     Plus,
@@ -68,21 +115,94 @@ pub enum Token<'src> {
 
     //Control
     Semicolon,
+    Comma,
+    Ellipsis,
+}
+
+/// The type of an integer constant, picked by the lexer from its suffix and value. Every
+/// such type is `int`, `long` or `long long`, for 0, 1 or 2 `longs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegerType {
+    pub signed: bool,
+    pub longs: u8,
+}
+
+impl IntegerType {
+    /// The largest value an object of this type holds
+    pub fn max(self) -> u64 {
+        match (self.signed, self.longs) {
+            (true, 0) => i32::MAX as u64,
+            (false, 0) => u32::MAX as u64,
+            (true, _) => i64::MAX as u64,
+            (false, _) => u64::MAX,
+        }
+    }
+}
+
+impl From<IntegerType> for Type {
+    fn from(IntegerType { signed, longs }: IntegerType) -> Self {
+        match longs {
+            0 => Type::Int { signed },
+            1 => Type::Long { signed },
+            _ => Type::LongLong { signed },
+        }
+    }
 }
 
 impl<'src> Display for Token<'src> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Int => write!(f, "int"),
+            Self::Char => write!(f, "char"),
+            Self::Short => write!(f, "short"),
+            Self::Long => write!(f, "long"),
+            Self::Float => write!(f, "float"),
+            Self::Double => write!(f, "double"),
+            Self::Void => write!(f, "void"),
+            Self::Signed => write!(f, "signed"),
+            Self::Unsigned => write!(f, "unsigned"),
             Self::Return => write!(f, "return"),
             Self::If => write!(f, "if"),
             Self::Else => write!(f, "else"),
-            Self::LitInteger(i) => write!(f, "{}", i),
+            Self::While => write!(f, "while"),
+            Self::Do => write!(f, "do"),
+            Self::For => write!(f, "for"),
+            Self::Break => write!(f, "break"),
+            Self::Continue => write!(f, "continue"),
+            Self::Sizeof => write!(f, "sizeof"),
+            Self::Alignof => write!(f, "_Alignof"),
+            Self::Struct => write!(f, "struct"),
+            Self::Union => write!(f, "union"),
+            Self::Enum => write!(f, "enum"),
+            Self::Typedef => write!(f, "typedef"),
+            Self::Static => write!(f, "static"),
+            Self::Extern => write!(f, "extern"),
+            Self::Const => write!(f, "const"),
+            Self::Volatile => write!(f, "volatile"),
+            Self::Switch => write!(f, "switch"),
+            Self::Case => write!(f, "case"),
+            Self::Default => write!(f, "default"),
+            Self::Goto => write!(f, "goto"),
+            Self::VaList => write!(f, "__builtin_va_list"),
+            Self::VaStart => write!(f, "__builtin_va_start"),
+            Self::VaArg => write!(f, "__builtin_va_arg"),
+            Self::VaEnd => write!(f, "__builtin_va_end"),
+            Self::LitInteger(i, _) => write!(f, "{}", i),
+            Self::LitFloat(bits, false) => write!(f, "{:?}", f64::from_bits(*bits)),
+            Self::LitFloat(bits, true) => write!(f, "{:?}f", f64::from_bits(*bits)),
+            Self::LitChar(c) => write!(f, "'{}'", c.escape_ascii()),
+            Self::LitString(s) => write!(f, "\"{}\"", s),
             Self::Identifier(s) => write!(f, "{}", s),
             Self::OpenParen => write!(f, "("),
             Self::CloseParen => write!(f, ")"),
             Self::OpenBrace => write!(f, "{{"),
             Self::CloseBrace => write!(f, "}}"),
+            Self::OpenBracket => write!(f, "["),
+            Self::CloseBracket => write!(f, "]"),
+            Self::Dot => write!(f, "."),
+            Self::Arrow => write!(f, "->"),
+            Self::PlusPlus => write!(f, "++"),
+            Self::MinusMinus => write!(f, "--"),
             Self::Plus => write!(f, "+"),
             Self::Minus => write!(f, "-"),
             Self::Star => write!(f, "*"),
@@ -117,6 +237,8 @@ impl<'src> Display for Token<'src> {
             Self::Colon => write!(f, ":"),
             Self::Question => write!(f, "?"),
             Self::Semicolon => write!(f, ";"),
+            Self::Comma => write!(f, ","),
+            Self::Ellipsis => write!(f, "..."),
         }
     }
 }
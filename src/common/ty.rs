@@ -0,0 +1,1017 @@
+use super::{
+    env::{Symbol, WORD_IN_BYTES},
+    BinaryOperator, CodegenError, Desugar, Environment, Expr, Span, Spanned, UnaryOperator,
+};
+use std::{cell::RefCell, fmt::Display, ops::BitOr, rc::Rc};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    /// Has no values, so no object can be declared with it. A pointer to it may point to
+    /// any object.
+    Void,
+    /// Plain `char` is signed, as on every System V target
+    Char {
+        signed: bool,
+    },
+    Short {
+        signed: bool,
+    },
+    Int {
+        signed: bool,
+    },
+    Long {
+        signed: bool,
+    },
+    LongLong {
+        signed: bool,
+    },
+    Float,
+    Double,
+    Pointer(Box<Self>),
+    Array(Box<Self>, usize),
+    Struct(StructRef),
+    /// A function returning the first type and taking parameters of the others, followed by
+    /// any number of variable arguments if it is variadic. A variadic function without
+    /// parameters was declared with `()`, leaving its parameters unspecified.
+    Function(Box<Self>, Vec<Self>, bool),
+    /// A type with qualifiers. The qualifiers of an array are those of its elements, so an
+    /// array is never qualified itself.
+    Qualified(Box<Self>, Qualifiers),
+}
+
+/// The qualifiers of a type. Every access to an object is emitted in order where the source
+/// makes it and no value is kept in a register across statements, so a `volatile` object is
+/// accessed exactly as written without any further care.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Qualifiers {
+    pub is_const: bool,
+    pub is_volatile: bool,
+}
+
+impl Qualifiers {
+    pub const CONST: Self = Self {
+        is_const: true,
+        is_volatile: false,
+    };
+
+    pub const VOLATILE: Self = Self {
+        is_const: false,
+        is_volatile: true,
+    };
+
+    pub fn is_empty(self) -> bool {
+        self == Self::default()
+    }
+
+    /// Whether these include every qualifier of `other`
+    pub fn contains(self, other: Self) -> bool {
+        self | other == self
+    }
+}
+
+impl BitOr for Qualifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            is_const: self.is_const || rhs.is_const,
+            is_volatile: self.is_volatile || rhs.is_volatile,
+        }
+    }
+}
+
+impl Display for Qualifiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = [(self.is_const, "const"), (self.is_volatile, "volatile")];
+        let names = names.iter().filter(|(set, _)| *set).map(|(_, name)| *name);
+        write!(f, "{}", names.collect::<Vec<_>>().join(" "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    pub offset: isize,
+}
+
+/// Which keyword introduced a tag. Struct, union and enum tags share one namespace.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TagKind {
+    #[default]
+    Struct,
+    Union,
+    Enum,
+}
+
+impl Display for TagKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Struct => write!(f, "struct"),
+            Self::Union => write!(f, "union"),
+            Self::Enum => write!(f, "enum"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StructDef {
+    pub kind: TagKind,
+    pub tag: Option<String>,
+    pub members: Option<Vec<Member>>,
+    pub size: isize,
+    pub align: isize,
+}
+
+/// Handle to a struct or union definition. All types naming the same struct share one
+/// definition, so completing a forward declared struct is visible through earlier pointers
+/// to it.
+#[derive(Clone)]
+pub struct StructRef(Rc<RefCell<StructDef>>);
+
+impl StructRef {
+    /// Creates an incomplete struct or union
+    pub fn new(kind: TagKind, tag: Option<String>) -> Self {
+        Self(Rc::new(RefCell::new(StructDef {
+            kind,
+            tag,
+            ..Default::default()
+        })))
+    }
+
+    pub fn kind(&self) -> TagKind {
+        self.0.borrow().kind
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.0.borrow().members.is_some()
+    }
+
+    /// Completes the struct, laying its members out in order following the System V ABI:
+    /// each member is placed at the next offset that satisfies its alignment, and the
+    /// struct is padded to a multiple of its strictest member alignment. Union members all
+    /// start at offset 0, the union being as large as its largest member.
+    pub fn define(&self, members: Vec<(String, Type)>) {
+        let union = self.kind() == TagKind::Union;
+        let mut size = 0;
+        let mut align = 1;
+        let members = members
+            .into_iter()
+            .map(|(name, ty)| {
+                let offset = if union { 0 } else { align_to(size, ty.align()) };
+                align = align.max(ty.align());
+                size = size.max(offset + ty.size());
+                Member { name, ty, offset }
+            })
+            .collect();
+
+        let mut def = self.0.borrow_mut();
+        def.members = Some(members);
+        def.size = align_to(size, align);
+        def.align = align;
+    }
+
+    pub fn members(&self) -> Vec<Member> {
+        self.0.borrow().members.clone().unwrap_or_default()
+    }
+
+    pub fn member(&self, name: &str) -> Option<Member> {
+        self.0
+            .borrow()
+            .members
+            .as_ref()?
+            .iter()
+            .find(|member| member.name == name)
+            .cloned()
+    }
+}
+
+impl PartialEq for StructRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for StructRef {}
+
+// Members may point back at the struct itself, so only the tag is printed
+impl std::fmt::Debug for StructRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "StructRef({:?})", self.0.borrow().tag)
+    }
+}
+
+impl Display for StructRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let def = self.0.borrow();
+        match &def.tag {
+            Some(tag) => write!(f, "{} {}", def.kind, tag),
+            None => write!(f, "{} <anonymous>", def.kind),
+        }
+    }
+}
+
+pub fn align_to(n: isize, align: isize) -> isize {
+    (n + align - 1) / align * align
+}
+
+impl Type {
+    pub fn pointer_to(self) -> Self {
+        Self::Pointer(Box::new(self))
+    }
+
+    pub fn array_of(self, len: usize) -> Self {
+        Self::Array(Box::new(self), len)
+    }
+
+    /// Adds `qualifiers` to this type, or to the elements of an array
+    pub fn qualified(self, qualifiers: Qualifiers) -> Self {
+        match self {
+            ty if qualifiers.is_empty() => ty,
+            Self::Array(elem, len) => elem.qualified(qualifiers).array_of(len),
+            Self::Qualified(ty, inner) => Self::Qualified(ty, inner | qualifiers),
+            ty => Self::Qualified(Box::new(ty), qualifiers),
+        }
+    }
+
+    /// Whether this is a struct with a `const` member, or a member that is such a struct,
+    /// which cannot be assigned as a whole
+    pub fn has_const_member(&self) -> bool {
+        match self.unqualified() {
+            Self::Struct(s) => s
+                .members()
+                .iter()
+                .any(|member| member.ty.qualifiers().is_const || member.ty.has_const_member()),
+            Self::Array(elem, _) => elem.has_const_member(),
+            _ => false,
+        }
+    }
+
+    pub fn qualifiers(&self) -> Qualifiers {
+        match self {
+            Self::Qualified(_, qualifiers) => *qualifiers,
+            Self::Array(elem, _) => elem.qualifiers(),
+            _ => Qualifiers::default(),
+        }
+    }
+
+    /// This type without its qualifiers, as the value of an object has
+    pub fn unqualified(&self) -> &Self {
+        match self {
+            Self::Qualified(ty, _) => ty,
+            ty => ty,
+        }
+    }
+
+    /// Converts an array to a pointer to its first element and a function to a pointer to
+    /// it, as happens to array and function expressions in most contexts
+    pub fn decay(self) -> Self {
+        match self {
+            Self::Array(elem, _) => Self::Pointer(elem),
+            ty @ Self::Function(_, _, _) => ty.pointer_to(),
+            Self::Qualified(ty, _) => ty.decay(),
+            ty => ty,
+        }
+    }
+
+    /// The type pointed to, without its qualifiers
+    pub fn pointee(&self) -> Option<&Self> {
+        match self.unqualified() {
+            Self::Pointer(ty) => Some(ty.unqualified()),
+            _ => None,
+        }
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        self.pointee().is_some()
+    }
+
+    /// Whether this is a function declared with `()`, leaving its parameters unspecified
+    pub fn is_unprototyped(&self) -> bool {
+        matches!(self, Self::Function(_, params, true) if params.is_empty())
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.rank().is_some()
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self.unqualified(), Self::Float | Self::Double)
+    }
+
+    pub fn is_void(&self) -> bool {
+        matches!(self.unqualified(), Self::Void)
+    }
+
+    pub fn is_arithmetic(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    /// Whether a value of this type can be compared with zero, as conditions are
+    pub fn is_scalar(&self) -> bool {
+        self.is_arithmetic() || self.is_pointer()
+    }
+
+    /// Whether arithmetic on this type is signed. Pointers compare as unsigned addresses.
+    pub fn is_signed(&self) -> bool {
+        match self.unqualified() {
+            Self::Char { signed }
+            | Self::Short { signed }
+            | Self::Int { signed }
+            | Self::Long { signed }
+            | Self::LongLong { signed } => *signed,
+            _ => false,
+        }
+    }
+
+    /// The integer conversion rank, ordering integer types by width
+    fn rank(&self) -> Option<u8> {
+        match self.unqualified() {
+            Self::Char { .. } => Some(1),
+            Self::Short { .. } => Some(2),
+            Self::Int { .. } => Some(3),
+            Self::Long { .. } => Some(4),
+            Self::LongLong { .. } => Some(5),
+            _ => None,
+        }
+    }
+
+    fn to_unsigned(&self) -> Self {
+        match self.unqualified() {
+            Self::Char { .. } => Self::Char { signed: false },
+            Self::Short { .. } => Self::Short { signed: false },
+            Self::Int { .. } => Self::Int { signed: false },
+            Self::Long { .. } => Self::Long { signed: false },
+            Self::LongLong { .. } => Self::LongLong { signed: false },
+            ty => ty.clone(),
+        }
+    }
+
+    /// Converts the integer `i` to this type, extended back to 64 bits as it is held in a
+    /// register
+    pub fn truncate(&self, i: i64) -> i64 {
+        match (self.size(), self.is_signed()) {
+            (1, true) => i as i8 as i64,
+            (1, false) => i as u8 as i64,
+            (2, true) => i as i16 as i64,
+            (2, false) => i as u16 as i64,
+            (4, true) => i as i32 as i64,
+            (4, false) => i as u32 as i64,
+            _ => i,
+        }
+    }
+
+    /// Whether a value of type `from` converts to this type implicitly, as when it is passed
+    /// as an argument. A pointer may gain qualifiers on what it points to, but never lose them.
+    pub fn accepts(&self, from: &Self) -> bool {
+        match (self.unqualified(), from.unqualified()) {
+            (Self::Pointer(to), Self::Pointer(from)) => {
+                let agree = match (to.unqualified(), from.unqualified()) {
+                    // Functions with unspecified parameters agree with any returning the same
+                    // type
+                    (Self::Function(a, ..), Self::Function(b, ..))
+                        if to.is_unprototyped() || from.is_unprototyped() =>
+                    {
+                        a == b
+                    }
+                    // Pointers to `void` convert to and from pointers to any object
+                    (Self::Void, ty) | (ty, Self::Void) => !ty.is_function(),
+                    (to, from) => to == from,
+                };
+                agree && to.qualifiers().contains(from.qualifiers())
+            }
+            (to, from) => (to.is_arithmetic() && from.is_arithmetic()) || to == from,
+        }
+    }
+
+    /// Applies the integer promotions: integers narrower than `int` become `int`, as every
+    /// value they hold fits in one
+    pub fn promote(self) -> Self {
+        match self.rank() {
+            Some(rank) if rank < 3 => Self::Int { signed: true },
+            _ => self.unqualified().clone(),
+        }
+    }
+
+    /// The type both operands of an arithmetic operator are converted to, following the
+    /// usual arithmetic conversions. Pointers, as compared by the relational operators, are
+    /// treated as `unsigned long`.
+    pub fn common(&self, other: &Self) -> Self {
+        let (this, other) = (self.unqualified(), other.unqualified());
+        if *this == Self::Double || *other == Self::Double {
+            return Self::Double;
+        }
+        if *this == Self::Float || *other == Self::Float {
+            return Self::Float;
+        }
+
+        let convert = |ty: &Self| match ty.rank() {
+            Some(_) => ty.clone().promote(),
+            None => Self::Long { signed: false },
+        };
+        let (a, b) = (convert(this), convert(other));
+
+        if a == b {
+            return a;
+        }
+        if a.is_signed() == b.is_signed() {
+            return if a.rank() >= b.rank() { a } else { b };
+        }
+
+        let (unsigned, signed) = if a.is_signed() { (b, a) } else { (a, b) };
+        if unsigned.rank() >= signed.rank() {
+            unsigned
+        } else if signed.size() > unsigned.size() {
+            signed
+        } else {
+            signed.to_unsigned()
+        }
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, Self::Array(_, _))
+    }
+
+    pub fn is_function(&self) -> bool {
+        matches!(self.unqualified(), Self::Function(_, _, _))
+    }
+
+    /// Aggregates evaluate to their address rather than being loaded into a register
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self.unqualified(), Self::Array(_, _) | Self::Struct(_))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match self.unqualified() {
+            Self::Void => false,
+            Self::Struct(s) => s.is_complete(),
+            Self::Array(elem, _) => elem.is_complete(),
+            _ => true,
+        }
+    }
+
+    /// Size in bytes of an object of this type
+    pub fn size(&self) -> isize {
+        match self {
+            Self::Char { .. } => 1,
+            Self::Short { .. } => 2,
+            Self::Int { .. } => 4,
+            Self::Long { .. } | Self::LongLong { .. } => 8,
+            Self::Float => 4,
+            Self::Double => 8,
+            Self::Pointer(_) => WORD_IN_BYTES,
+            Self::Array(elem, len) => elem.size() * *len as isize,
+            Self::Struct(s) => s.0.borrow().size,
+            // As in GNU C, so that arithmetic on `void` and function pointers steps by bytes
+            Self::Void | Self::Function(_, _, _) => 1,
+            Self::Qualified(ty, _) => ty.size(),
+        }
+    }
+
+    /// Alignment in bytes of an object of this type
+    pub fn align(&self) -> isize {
+        match self {
+            Self::Pointer(_) => WORD_IN_BYTES,
+            Self::Array(elem, _) => elem.align(),
+            Self::Struct(s) => s.0.borrow().align,
+            Self::Qualified(ty, _) => ty.align(),
+            // Every arithmetic type is aligned to its size
+            ty => ty.size(),
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Void => write!(f, "void"),
+            Self::Char { signed: true } => write!(f, "char"),
+            Self::Char { signed: false } => write!(f, "unsigned char"),
+            Self::Short { signed: true } => write!(f, "short"),
+            Self::Short { signed: false } => write!(f, "unsigned short"),
+            Self::Int { signed: true } => write!(f, "int"),
+            Self::Int { signed: false } => write!(f, "unsigned int"),
+            Self::Long { signed: true } => write!(f, "long"),
+            Self::Long { signed: false } => write!(f, "unsigned long"),
+            Self::LongLong { signed: true } => write!(f, "long long"),
+            Self::LongLong { signed: false } => write!(f, "unsigned long long"),
+            Self::Float => write!(f, "float"),
+            Self::Double => write!(f, "double"),
+            Self::Struct(s) => write!(f, "{}", s),
+            Self::Function(ret, params, variadic) => {
+                write!(f, "{} ({})", ret, ParamList(params, *variadic))
+            }
+            Self::Pointer(ty) => match &**ty {
+                Self::Function(ret, params, variadic) => {
+                    write!(f, "{} (*)({})", ret, ParamList(params, *variadic))
+                }
+                ty @ Self::Pointer(_) => write!(f, "{}*", ty),
+                ty => write!(f, "{} *", ty),
+            },
+            // Qualifiers of a pointer follow its `*`
+            Self::Qualified(ty, qualifiers) if ty.is_pointer() => write!(f, "{}{}", ty, qualifiers),
+            Self::Qualified(ty, qualifiers) => write!(f, "{} {}", qualifiers, ty),
+            Self::Array(_, _) => {
+                let mut elem = self;
+                let mut dims = String::new();
+                while let Self::Array(inner, len) = elem {
+                    dims += &format!("[{}]", len);
+                    elem = inner;
+                }
+                write!(f, "{}{}", elem, dims)
+            }
+        }
+    }
+}
+
+/// Formats parameter types separated by commas, ending with `...` if variadic
+struct ParamList<'a>(&'a [Type], bool);
+
+impl Display for ParamList<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self([], false) => return write!(f, "void"),
+            Self([], true) => return Ok(()),
+            _ => {}
+        }
+        let variadic = self.1.then_some("...");
+        let params = self
+            .0
+            .iter()
+            .map(|ty| ty.to_string())
+            .chain(variadic.map(Into::into));
+        write!(f, "{}", params.collect::<Vec<_>>().join(", "))
+    }
+}
+
+/// Looks up the member `name` of the struct `lhs` evaluates to
+pub fn member_of<'src>(
+    lhs: &Spanned<Expr<'src>>,
+    (name, name_span): Spanned<&'src str>,
+    env: &Environment<'src>,
+) -> Result<Member, Spanned<CodegenError<'src>>> {
+    match lhs.ty(env)? {
+        Type::Struct(s) if !s.is_complete() => {
+            Err((CodegenError::IncompleteType(Type::Struct(s)), lhs.1))
+        }
+        Type::Struct(s) => s.member(name).ok_or((
+            CodegenError::UnknownMember(name, Type::Struct(s)),
+            name_span,
+        )),
+        ty => Err((CodegenError::InvalidMemberAccess(ty), lhs.1)),
+    }
+}
+
+/// The type of the object `expr` designates, keeping the qualifiers [`TypeOf::ty`] drops as
+/// the value of the object is read. A member has the qualifiers of the struct it belongs to
+/// as well as its own.
+pub fn object_ty<'src>(
+    expr: &Spanned<Expr<'src>>,
+    env: &Environment<'src>,
+) -> Result<Type, Spanned<CodegenError<'src>>> {
+    match expr {
+        (Expr::Variable(name), _) => match env.get_symbol(name) {
+            Some(Symbol::Variable(var)) => Ok(var.ty),
+            _ => expr.ty(env),
+        },
+        (Expr::Unary(UnaryOperator::Deref, rhs), _) => match rhs.ty(env)?.decay() {
+            Type::Pointer(ty) => Ok(*ty),
+            _ => expr.ty(env),
+        },
+        (Expr::Member(lhs, name), _) => {
+            let qualifiers = object_ty(lhs, env)?.qualifiers();
+            Ok(member_of(lhs, *name, env)?.ty.qualified(qualifiers))
+        }
+        (Expr::Index(_, _), _) => object_ty(&expr.clone().desugar().expect("infallible")[0], env),
+        _ => expr.ty(env),
+    }
+}
+
+/// Checks that the operands of `op`, or of the operator applied by the compound assignment
+/// `op`, have types it operates on
+pub fn check_operands<'src>(
+    op: BinaryOperator,
+    lhs: &Spanned<Expr<'src>>,
+    rhs: &Spanned<Expr<'src>>,
+    span: Span,
+    env: &Environment<'src>,
+) -> Result<(), Spanned<CodegenError<'src>>> {
+    let (lhs_ty, rhs_ty) = (lhs.ty(env)?.decay(), rhs.ty(env)?.decay());
+    let valid = match op.compound_to_operator().unwrap_or(op) {
+        // A pointer moves by an integer number of elements. Only `p - q` gives a difference,
+        // which is only measured between pointers to the same type.
+        BinaryOperator::Plus if !op.is_compound_assignment() && rhs_ty.is_pointer() => {
+            lhs_ty.is_integer()
+        }
+        BinaryOperator::Minus if !op.is_compound_assignment() && rhs_ty.is_pointer() => {
+            lhs_ty.pointee().is_some() && lhs_ty.pointee() == rhs_ty.pointee()
+        }
+        BinaryOperator::Plus | BinaryOperator::Minus if lhs_ty.is_pointer() => rhs_ty.is_integer(),
+        // Pointers compare with pointers, and with integers such as a null pointer constant
+        BinaryOperator::EqEq
+        | BinaryOperator::Ne
+        | BinaryOperator::Gt
+        | BinaryOperator::Lt
+        | BinaryOperator::Ge
+        | BinaryOperator::Le
+            if lhs_ty.is_pointer() || rhs_ty.is_pointer() =>
+        {
+            (lhs_ty.is_pointer() || lhs_ty.is_integer())
+                && (rhs_ty.is_pointer() || rhs_ty.is_integer())
+        }
+        BinaryOperator::Plus
+        | BinaryOperator::Minus
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::EqEq
+        | BinaryOperator::Ne
+        | BinaryOperator::Gt
+        | BinaryOperator::Lt
+        | BinaryOperator::Ge
+        | BinaryOperator::Le => lhs_ty.is_arithmetic() && rhs_ty.is_arithmetic(),
+        BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+            lhs_ty.is_scalar() && rhs_ty.is_scalar()
+        }
+        // Remainders, bits and shifts only exist for integers
+        BinaryOperator::Mod
+        | BinaryOperator::BitwiseAnd
+        | BinaryOperator::BitwiseOr
+        | BinaryOperator::BitwiseXor
+        | BinaryOperator::LeftShift
+        | BinaryOperator::RightShift => lhs_ty.is_integer() && rhs_ty.is_integer(),
+        _ => true,
+    };
+    match valid {
+        true => Ok(()),
+        false => Err((CodegenError::InvalidOperands(lhs_ty, rhs_ty), span)),
+    }
+}
+
+/// Checks that the operand of the arithmetic or logical `op` has a type it operates on
+pub fn check_operand<'src>(
+    op: UnaryOperator,
+    rhs: &Spanned<Expr<'src>>,
+    span: Span,
+    env: &Environment<'src>,
+) -> Result<(), Spanned<CodegenError<'src>>> {
+    let ty = rhs.ty(env)?.decay();
+    let valid = match op {
+        UnaryOperator::Plus | UnaryOperator::Minus => ty.is_arithmetic(),
+        UnaryOperator::BitwiseNot => ty.is_integer(),
+        UnaryOperator::LogicalNot => ty.is_scalar(),
+        _ => true,
+    };
+    match valid {
+        true => Ok(()),
+        false => Err((CodegenError::InvalidOperand(ty), span)),
+    }
+}
+
+/// The type of a condition, which is compared with zero and so must be a scalar
+pub fn condition_ty<'src>(
+    condition: &Spanned<Expr<'src>>,
+    env: &Environment<'src>,
+) -> Result<Type, Spanned<CodegenError<'src>>> {
+    match condition.ty(env)?.decay() {
+        ty if ty.is_scalar() => Ok(ty),
+        ty => Err((CodegenError::NonScalarCondition(ty), condition.1)),
+    }
+}
+
+pub trait TypeOf<'src> {
+    /// Infers the type of an expression without generating any code for it
+    fn ty(&self, env: &Environment<'src>) -> Result<Type, Spanned<CodegenError<'src>>>;
+}
+
+impl<'src> TypeOf<'src> for Spanned<Expr<'src>> {
+    fn ty(&self, env: &Environment<'src>) -> Result<Type, Spanned<CodegenError<'src>>> {
+        let int = Type::Int { signed: true };
+
+        Ok(match self {
+            (Expr::LiteralInteger(_, ty), _) | (Expr::LiteralFloat(_, ty), _) => ty.clone(),
+            // The array includes the terminating null
+            (Expr::LiteralString(bytes), _) => {
+                Type::Char { signed: true }.array_of(bytes.len() + 1)
+            }
+
+            (Expr::Variable(name), span) => match env.get_symbol(name) {
+                Some(Symbol::Variable(var)) => var.ty.unqualified().clone(),
+                Some(Symbol::Enumerator(_, _)) => int,
+                Some(Symbol::Typedef(_, _)) => {
+                    return Err((CodegenError::UndeclaredVariable(name), *span))
+                }
+                None => match env.get_function(name) {
+                    Some(function) => function.ty(),
+                    None => return Err((CodegenError::UndeclaredVariable(name), *span)),
+                },
+            },
+
+            (Expr::Unary(UnaryOperator::Deref, rhs), span) => match rhs.ty(env)?.decay() {
+                Type::Pointer(ty) if !ty.is_void() => ty.unqualified().clone(),
+                ty => return Err((CodegenError::InvalidDereference(ty), *span)),
+            },
+
+            (Expr::Unary(UnaryOperator::AddressOf, rhs), _) => object_ty(rhs, env)?.pointer_to(),
+
+            (Expr::Unary(UnaryOperator::LogicalNot, rhs), span) => {
+                check_operand(UnaryOperator::LogicalNot, rhs, *span, env)?;
+                int
+            }
+
+            // The result is the value stored back, which has the type of the operand
+            (Expr::Unary(op, rhs), _) if op.is_increment() => rhs.ty(env)?,
+
+            (Expr::Unary(op, rhs), span) => {
+                check_operand(*op, rhs, *span, env)?;
+                rhs.ty(env)?.promote()
+            }
+
+            (Expr::Binary(lhs, op, rhs), span) => {
+                check_operands(*op, lhs, rhs, *span, env)?;
+                match op {
+                    BinaryOperator::Plus => match (lhs.ty(env)?.decay(), rhs.ty(env)?.decay()) {
+                        (ty @ Type::Pointer(_), _) | (_, ty @ Type::Pointer(_)) => ty,
+                        (lhs, rhs) => lhs.common(&rhs),
+                    },
+                    BinaryOperator::Minus => match (lhs.ty(env)?.decay(), rhs.ty(env)?.decay()) {
+                        (Type::Pointer(_), Type::Pointer(_)) => Type::Long { signed: true },
+                        (ty @ Type::Pointer(_), _) => ty,
+                        (lhs, rhs) => lhs.common(&rhs),
+                    },
+                    BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Mod
+                    | BinaryOperator::BitwiseAnd
+                    | BinaryOperator::BitwiseOr
+                    | BinaryOperator::BitwiseXor => lhs.ty(env)?.common(&rhs.ty(env)?),
+                    // The type of a shift is that of its left operand alone
+                    BinaryOperator::LeftShift | BinaryOperator::RightShift => {
+                        lhs.ty(env)?.promote()
+                    }
+                    op if *op == BinaryOperator::Eq || op.is_compound_assignment() => {
+                        lhs.ty(env)?
+                    }
+                    BinaryOperator::Comma => rhs.ty(env)?.decay(),
+                    _ => int,
+                }
+            }
+
+            (Expr::Ternary(_, a, b), _) => match (a.ty(env)?.decay(), b.ty(env)?.decay()) {
+                (a, b) if a.is_arithmetic() && b.is_arithmetic() => a.common(&b),
+                // The other operand is then a null pointer
+                (a, b) if b.is_pointer() && !a.is_pointer() => b,
+                (a, _) => a,
+            },
+
+            (Expr::SizeOf(_) | Expr::SizeOfType(_) | Expr::AlignOf(_), _) => {
+                Type::Long { signed: false }
+            }
+
+            (Expr::Cast(ty, _), span) | (Expr::VaArg(_, ty), span) => {
+                ty.lookup(env, *span)?.unqualified().clone()
+            }
+
+            (Expr::VaStart(..) | Expr::VaEnd(_), _) => int,
+
+            (Expr::Member(lhs, name), _) => member_of(lhs, *name, env)?.ty.unqualified().clone(),
+
+            (Expr::Index(_, _), _) => self.clone().desugar().expect("infallible")[0].ty(env)?,
+
+            // Functions that were never declared are assumed to return int
+            (Expr::Call(callee, _), _) => match &**callee {
+                (Expr::Variable(name), _) if !env.contains(name) => {
+                    env.get_function(name).map_or(int, |f| f.ret)
+                }
+                callee => match callee.ty(env)?.decay() {
+                    Type::Pointer(ty) => match *ty {
+                        Type::Function(ret, _, _) => *ret,
+                        _ => return Err((CodegenError::InvalidCallTarget, callee.1)),
+                    },
+                    _ => return Err((CodegenError::InvalidCallTarget, callee.1)),
+                },
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INT: Type = Type::Int { signed: true };
+
+    #[test]
+    fn size() {
+        assert_eq!(INT.size(), 4);
+        assert_eq!(INT.pointer_to().size(), 8);
+        assert_eq!(INT.pointer_to().pointer_to().size(), 8);
+        assert_eq!(INT.array_of(10).size(), 40);
+        assert_eq!(INT.array_of(4).array_of(3).size(), 48);
+        assert_eq!(INT.pointer_to().array_of(3).size(), 24);
+        assert_eq!(Type::Char { signed: true }.size(), 1);
+        assert_eq!(Type::Char { signed: false }.array_of(5).size(), 5);
+        assert_eq!(Type::Short { signed: true }.size(), 2);
+        assert_eq!(Type::Long { signed: false }.size(), 8);
+        assert_eq!(Type::LongLong { signed: true }.align(), 8);
+    }
+
+    #[test]
+    fn struct_layout() {
+        let s = StructRef::new(TagKind::Struct, Some("s".into()));
+        assert!(!s.is_complete());
+
+        s.define(vec![
+            ("a".into(), INT),
+            ("b".into(), INT.array_of(3)),
+            ("c".into(), INT.pointer_to()),
+        ]);
+        assert!(s.is_complete());
+        assert_eq!(s.member("a").map(|m| m.offset), Some(0));
+        assert_eq!(s.member("b").map(|m| m.offset), Some(4));
+        assert_eq!(s.member("c").map(|m| m.offset), Some(16));
+        assert_eq!(s.member("d"), None);
+
+        let ty = Type::Struct(s);
+        assert_eq!(ty.size(), 24);
+        assert_eq!(ty.align(), 8);
+        assert_eq!(ty.to_string(), "struct s");
+    }
+
+    #[test]
+    fn union_layout() {
+        let u = StructRef::new(TagKind::Union, Some("u".into()));
+        u.define(vec![
+            ("a".into(), INT),
+            ("b".into(), INT.array_of(3)),
+            ("c".into(), INT.pointer_to()),
+        ]);
+        assert_eq!(u.member("a").map(|m| m.offset), Some(0));
+        assert_eq!(u.member("b").map(|m| m.offset), Some(0));
+        assert_eq!(u.member("c").map(|m| m.offset), Some(0));
+
+        let ty = Type::Struct(u);
+        assert_eq!(ty.size(), 16);
+        assert_eq!(ty.align(), 8);
+        assert_eq!(ty.to_string(), "union u");
+    }
+
+    #[test]
+    fn struct_identity() {
+        let a = Type::Struct(StructRef::new(TagKind::Struct, Some("s".into())));
+        let b = Type::Struct(StructRef::new(TagKind::Struct, Some("s".into())));
+        assert_eq!(a, a.clone());
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn arithmetic_conversions() {
+        let uint = Type::Int { signed: false };
+        let long = Type::Long { signed: true };
+        let ulong = Type::Long { signed: false };
+        let char = Type::Char { signed: true };
+        let short = Type::Short { signed: false };
+
+        assert_eq!(char.clone().promote(), INT);
+        assert_eq!(short.clone().promote(), INT);
+        assert_eq!(uint.clone().promote(), uint);
+        assert_eq!(char.common(&short), INT);
+        assert_eq!(INT.common(&uint), uint);
+        assert_eq!(uint.common(&long), long);
+        assert_eq!(long.common(&ulong), ulong);
+        assert_eq!(
+            Type::LongLong { signed: true }.common(&ulong),
+            Type::LongLong { signed: false }
+        );
+        assert_eq!(INT.pointer_to().common(&INT), ulong);
+        assert_eq!(ulong.common(&Type::Float), Type::Float);
+        assert_eq!(Type::Float.common(&Type::Double), Type::Double);
+    }
+
+    #[test]
+    fn decay() {
+        assert_eq!(INT.decay(), INT);
+        assert_eq!(INT.array_of(10).decay(), INT.pointer_to());
+        assert_eq!(
+            INT.array_of(4).array_of(3).decay(),
+            INT.array_of(4).pointer_to()
+        );
+
+        let function = Type::Function(Box::new(INT), vec![INT], false);
+        assert_eq!(function.clone().decay(), function.pointer_to());
+    }
+
+    #[test]
+    fn pointee() {
+        assert_eq!(INT.pointee(), None);
+        assert_eq!(INT.pointer_to().pointee(), Some(&INT));
+        assert_eq!(
+            INT.pointer_to().pointer_to().pointee(),
+            Some(&INT.pointer_to())
+        );
+    }
+
+    #[test]
+    fn void_pointers() {
+        let void = Type::Void.pointer_to();
+        let const_int = INT.qualified(Qualifiers::CONST).pointer_to();
+        let function = Type::Function(Box::new(INT), vec![], false).pointer_to();
+        assert!(!Type::Void.is_complete());
+        assert_eq!(void.to_string(), "void *");
+
+        // Object pointers convert to and from `void *`, keeping their qualifiers
+        assert!(void.accepts(&INT.pointer_to()));
+        assert!(INT.pointer_to().accepts(&void));
+        assert!(const_int.accepts(&void));
+        assert!(!void.accepts(&const_int));
+        assert!(Type::Void
+            .qualified(Qualifiers::CONST)
+            .pointer_to()
+            .accepts(&const_int));
+        assert!(!void.accepts(&function));
+        assert!(!function.accepts(&void));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(INT.to_string(), "int");
+        assert_eq!(
+            Type::LongLong { signed: false }.to_string(),
+            "unsigned long long"
+        );
+        assert_eq!(INT.pointer_to().pointer_to().to_string(), "int **");
+        assert_eq!(INT.array_of(4).array_of(3).to_string(), "int[3][4]");
+
+        let function = Type::Function(Box::new(INT.pointer_to()), vec![INT, Type::Double], false);
+        assert_eq!(function.to_string(), "int * (int, double)");
+        assert_eq!(function.pointer_to().to_string(), "int * (*)(int, double)");
+
+        let function = Type::Function(Box::new(INT), vec![INT.pointer_to()], true);
+        assert_eq!(function.pointer_to().to_string(), "int (*)(int *, ...)");
+
+        assert_eq!(
+            Type::Function(Box::new(INT), vec![], false).to_string(),
+            "int (void)"
+        );
+        assert_eq!(
+            Type::Function(Box::new(INT), vec![], true).to_string(),
+            "int ()"
+        );
+    }
+
+    #[test]
+    fn unprototyped() {
+        let unspecified = Type::Function(Box::new(INT), vec![], true);
+        let binary = Type::Function(Box::new(INT), vec![INT, INT], false);
+        assert!(unspecified.is_unprototyped());
+        assert!(!binary.is_unprototyped());
+
+        let (unspecified, binary) = (unspecified.pointer_to(), binary.pointer_to());
+        assert!(unspecified.accepts(&binary));
+        assert!(binary.accepts(&unspecified));
+
+        let returns_double = Type::Function(Box::new(Type::Double), vec![], false);
+        assert!(!unspecified
+            .pointer_to()
+            .accepts(&returns_double.pointer_to()));
+    }
+
+    #[test]
+    fn qualifiers() {
+        let both = Qualifiers::CONST | Qualifiers::VOLATILE;
+        let const_int = INT.qualified(Qualifiers::CONST);
+        assert_eq!(const_int.to_string(), "const int");
+        assert_eq!(const_int.unqualified(), &INT);
+        assert_eq!(const_int.size(), 4);
+        assert_eq!(INT.qualified(Qualifiers::default()), INT);
+        assert_eq!(
+            const_int.clone().qualified(Qualifiers::VOLATILE),
+            INT.qualified(both)
+        );
+        assert_eq!(INT.qualified(both).to_string(), "const volatile int");
+
+        // The elements of an array carry its qualifiers
+        let array = INT.array_of(3).qualified(Qualifiers::CONST);
+        assert_eq!(array, const_int.clone().array_of(3));
+        assert_eq!(array.qualifiers(), Qualifiers::CONST);
+        assert_eq!(array.decay(), const_int.clone().pointer_to());
+
+        let const_ptr = INT.pointer_to().qualified(Qualifiers::CONST);
+        assert_eq!(const_ptr.to_string(), "int *const");
+        assert_eq!(const_ptr.pointer_to().to_string(), "int *const *");
+        assert_eq!(const_int.clone().pointer_to().to_string(), "const int *");
+        assert_eq!(const_int.clone().pointer_to().pointee(), Some(&INT));
+
+        // Pointers may gain qualifiers on what they point to, but not lose them
+        assert!(const_int.clone().pointer_to().accepts(&INT.pointer_to()));
+        assert!(!INT.pointer_to().accepts(&const_int.clone().pointer_to()));
+        assert!(INT.accepts(&INT.qualified(both)));
+
+        // A struct with a const member, however deep, cannot be assigned as a whole
+        let inner = StructRef::new(TagKind::Struct, None);
+        inner.define(vec![("a".into(), INT), ("b".into(), const_int)]);
+        let outer = StructRef::new(TagKind::Struct, None);
+        outer.define(vec![("in".into(), Type::Struct(inner).array_of(2))]);
+        assert!(Type::Struct(outer).has_const_member());
+        assert!(!INT.array_of(2).has_const_member());
+    }
+}
@@ -0,0 +1,275 @@
+use super::{
+    env::{Symbol, Tag},
+    ty::{Qualifiers, StructRef, TagKind},
+    CodegenError, ConstEval, Environment, Expr, Span, Spanned, Type,
+};
+
+/// A type as written in the source. Tags are scoped, so this is only resolved to a [`Type`]
+/// during codegen once the tags visible at that point are known.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeSpec<'src> {
+    Void,
+    Char {
+        signed: bool,
+    },
+    Short {
+        signed: bool,
+    },
+    Int {
+        signed: bool,
+    },
+    Long {
+        signed: bool,
+    },
+    LongLong {
+        signed: bool,
+    },
+    Float,
+    Double,
+    /// A struct or union
+    Struct(
+        TagKind,
+        Option<Spanned<&'src str>>,
+        Option<Vec<StructMember<'src>>>,
+    ),
+    Enum(Option<Spanned<&'src str>>, Option<Vec<Enumerator<'src>>>),
+    /// A name declared with `typedef`
+    Typedef(Spanned<&'src str>),
+    Pointer(Box<Self>),
+    /// The length must be an integer constant expression
+    Array(Box<Self>, Box<Spanned<Expr<'src>>>),
+    /// A function returning the first type and taking parameters of the others, and whether
+    /// it is variadic
+    Function(Box<Self>, Vec<Self>, bool),
+    /// The builtin `va_list`
+    VaList,
+    Qualified(Box<Self>, Qualifiers),
+}
+
+pub type StructMember<'src> = (TypeSpec<'src>, Spanned<&'src str>);
+
+/// An enumerator with its explicit value, if any
+pub type Enumerator<'src> = (Spanned<&'src str>, Option<Spanned<Expr<'src>>>);
+
+impl<'src> TypeSpec<'src> {
+    pub fn pointer_to(self) -> Self {
+        Self::Pointer(Box::new(self))
+    }
+
+    pub fn array_of(self, len: Spanned<Expr<'src>>) -> Self {
+        Self::Array(Box::new(self), Box::new(len))
+    }
+
+    pub fn qualified(self, qualifiers: Qualifiers) -> Self {
+        match qualifiers.is_empty() {
+            true => self,
+            false => Self::Qualified(Box::new(self), qualifiers),
+        }
+    }
+
+    /// Resolves this like [`TypeSpec::resolve`] where the environment cannot change, as for
+    /// a type name within an expression. Tags it declares are not kept.
+    pub fn lookup(
+        &self,
+        env: &Environment<'src>,
+        span: Span,
+    ) -> Result<Type, Spanned<CodegenError<'src>>> {
+        self.clone().resolve(&mut env.clone(), span)
+    }
+
+    /// Resolves this to a [`Type`], declaring or defining any tags and enumerators it
+    /// introduces in the current scope. `span` is reported for errors that have no better
+    /// location.
+    pub fn resolve(
+        self,
+        env: &mut Environment<'src>,
+        span: Span,
+    ) -> Result<Type, Spanned<CodegenError<'src>>> {
+        Ok(match self {
+            TypeSpec::Void => Type::Void,
+            TypeSpec::Char { signed } => Type::Char { signed },
+            TypeSpec::Short { signed } => Type::Short { signed },
+            TypeSpec::Int { signed } => Type::Int { signed },
+            TypeSpec::Long { signed } => Type::Long { signed },
+            TypeSpec::LongLong { signed } => Type::LongLong { signed },
+            TypeSpec::Float => Type::Float,
+            TypeSpec::Double => Type::Double,
+
+            TypeSpec::Typedef((name, _)) => match env.get_symbol(name) {
+                Some(Symbol::Typedef(ty, _)) => ty,
+                _ => unreachable!("parser only accepts typedef names in scope"),
+            },
+
+            TypeSpec::Pointer(ty) => ty.resolve(env, span)?.pointer_to(),
+
+            TypeSpec::Array(elem, len) => {
+                let elem = elem.resolve(env, span)?;
+                resolve_array(elem, &len, env, span)?
+            }
+
+            TypeSpec::Function(ret, params, variadic) => {
+                let ret = ret.resolve(env, span)?;
+                resolve_function(ret, &params, variadic, env, span)?
+            }
+
+            TypeSpec::VaList => env.va_list(),
+
+            TypeSpec::Qualified(ty, qualifiers) => ty.resolve(env, span)?.qualified(qualifiers),
+
+            // A reference to a tag that is not in scope declares it
+            TypeSpec::Struct(kind, Some((tag, tag_span)), None) => match env.get_tag(tag) {
+                Some((Tag::Struct(s), _)) if s.kind() == kind => Type::Struct(s),
+                Some((tag_def, init_span)) => {
+                    return Err((
+                        CodegenError::MismatchedTag(tag_def.kind(), tag, init_span),
+                        tag_span,
+                    ))
+                }
+                None => {
+                    let s = StructRef::new(kind, Some(tag.to_owned()));
+                    env.put_tag(tag, Tag::Struct(s.clone()), tag_span);
+                    Type::Struct(s)
+                }
+            },
+
+            TypeSpec::Struct(kind, tag, Some(members)) => {
+                let s = match tag {
+                    Some((tag, tag_span)) => match env.get_tag_in_scope(tag) {
+                        Some((tag_def, init_span)) if tag_def.kind() != kind => {
+                            return Err((
+                                CodegenError::MismatchedTag(tag_def.kind(), tag, init_span),
+                                tag_span,
+                            ))
+                        }
+                        Some((tag_def, init_span)) if tag_def.is_complete() => {
+                            return Err((
+                                CodegenError::RedefinedTag(kind, tag, init_span),
+                                tag_span,
+                            ))
+                        }
+                        Some((Tag::Struct(s), _)) => s,
+                        _ => StructRef::new(kind, Some(tag.to_owned())),
+                    },
+                    None => StructRef::new(kind, None),
+                };
+                // Declared before the members so they can point back at the struct
+                if let Some((tag, tag_span)) = tag {
+                    env.put_tag(tag, Tag::Struct(s.clone()), tag_span);
+                }
+
+                let mut resolved: Vec<(String, Type)> = vec![];
+                let mut seen: Vec<Spanned<&'src str>> = vec![];
+                for (ty, (name, name_span)) in members {
+                    if let Some((_, init_span)) = seen.iter().find(|(seen, _)| *seen == name) {
+                        return Err((CodegenError::DuplicateMember(name, *init_span), name_span));
+                    }
+                    let ty = ty.resolve(env, name_span)?;
+                    if !ty.is_complete() {
+                        return Err((CodegenError::IncompleteType(ty), name_span));
+                    }
+                    seen.push((name, name_span));
+                    resolved.push((name.to_owned(), ty));
+                }
+                s.define(resolved);
+
+                Type::Struct(s)
+            }
+
+            TypeSpec::Struct(_, None, None) => unreachable!("parser rejects 'struct' alone"),
+
+            // Enums are ints, the tag only matters for checking it is used consistently
+            TypeSpec::Enum(Some((tag, tag_span)), None) => match env.get_tag(tag) {
+                Some((Tag::Enum(_), _)) => Type::Int { signed: true },
+                Some((tag_def, init_span)) => {
+                    return Err((
+                        CodegenError::MismatchedTag(tag_def.kind(), tag, init_span),
+                        tag_span,
+                    ))
+                }
+                None => {
+                    env.put_tag(tag, Tag::Enum(false), tag_span);
+                    Type::Int { signed: true }
+                }
+            },
+
+            TypeSpec::Enum(tag, Some(enumerators)) => {
+                if let Some((tag, tag_span)) = tag {
+                    match env.get_tag_in_scope(tag) {
+                        Some((tag_def, init_span)) if tag_def.kind() != TagKind::Enum => {
+                            return Err((
+                                CodegenError::MismatchedTag(tag_def.kind(), tag, init_span),
+                                tag_span,
+                            ))
+                        }
+                        Some((tag_def, init_span)) if tag_def.is_complete() => {
+                            return Err((
+                                CodegenError::RedefinedTag(TagKind::Enum, tag, init_span),
+                                tag_span,
+                            ))
+                        }
+                        _ => env.put_tag(tag, Tag::Enum(true), tag_span),
+                    }
+                }
+
+                // Each enumerator without a value is one more than the previous one
+                let mut next = 0;
+                for ((name, name_span), value) in enumerators {
+                    let value = match value {
+                        Some(value) => value.eval(env)?,
+                        None => next,
+                    };
+                    if !env.put_enumerator(name, value, name_span) {
+                        let init_span = env.get_in_scope(name).expect("infallible").span();
+                        return Err((CodegenError::RedeclaredVariable(name, init_span), name_span));
+                    }
+                    next = value.wrapping_add(1);
+                }
+
+                Type::Int { signed: true }
+            }
+
+            TypeSpec::Enum(None, None) => unreachable!("parser rejects 'enum' alone"),
+        })
+    }
+}
+
+/// The type of a function returning `ret`. Tags declared among its parameters are only in
+/// scope until the end of the parameter list. Parameters of array type, as a `va_list` is,
+/// are adjusted to pointers, and the qualifiers of parameters and of the return type are no
+/// part of the type of the function.
+pub fn resolve_function<'src>(
+    ret: Type,
+    params: &[TypeSpec<'src>],
+    variadic: bool,
+    env: &Environment<'src>,
+    span: Span,
+) -> Result<Type, Spanned<CodegenError<'src>>> {
+    let params = params
+        .iter()
+        .map(|param| match param.lookup(env, span)? {
+            ty if ty.is_void() => Err((CodegenError::IncompleteType(ty), span)),
+            ty => Ok(ty.decay()),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Type::Function(
+        Box::new(ret.unqualified().clone()),
+        params,
+        variadic,
+    ))
+}
+
+/// The type of an array of `elem`, with the length given by the constant expression `len`
+pub fn resolve_array<'src>(
+    elem: Type,
+    len: &Spanned<Expr<'src>>,
+    env: &Environment<'src>,
+    span: Span,
+) -> Result<Type, Spanned<CodegenError<'src>>> {
+    if !elem.is_complete() {
+        return Err((CodegenError::IncompleteType(elem), span));
+    }
+    match len.eval(env)? {
+        n if n < 0 => Err((CodegenError::InvalidArrayLength(n), len.1)),
+        n => Ok(elem.array_of(n as usize)),
+    }
+}
@@ -1,19 +1,83 @@
-use crate::common::{Span, Token};
+use crate::common::{token::IntegerType, Span, Token};
 use chumsky::{prelude::*, text::digits};
 
 pub fn lexer<'src>(
 ) -> impl Parser<'src, &'src str, Vec<(Token<'src>, Span)>, extra::Err<Rich<'src, char, Span>>> {
     let literal = {
+        // A decimal floating constant needs a fraction or an exponent to tell it apart
+        // from an integer, a hexadecimal one always has a binary exponent
+        let float = {
+            let exponent = one_of("eE").then(one_of("+-").or_not()).then(digits(10));
+            let decimal = choice((
+                digits(10)
+                    .then(just('.'))
+                    .then(digits(10).or_not())
+                    .then(exponent.or_not())
+                    .ignored(),
+                just('.').then(digits(10)).then(exponent.or_not()).ignored(),
+                digits(10).then(exponent).ignored(),
+            ))
+            .to_slice()
+            .map(|src: &str| src.parse::<f64>().expect("infallible"));
+
+            let hex = just("0x")
+                .ignore_then(
+                    choice((
+                        digits(16)
+                            .then(just('.').then(digits(16).or_not()).or_not())
+                            .ignored(),
+                        just('.').then(digits(16)).ignored(),
+                    ))
+                    .to_slice(),
+                )
+                .then_ignore(one_of("pP"))
+                .then(one_of("+-").or_not().then(digits(10)).to_slice())
+                .map(|(mantissa, exponent)| hex_float(mantissa, exponent));
+
+            choice((hex, decimal))
+                .then(one_of("fF").or_not())
+                .map(|(value, suffix)| match suffix {
+                    Some(_) => Token::LitFloat((value as f32 as f64).to_bits(), true),
+                    None => Token::LitFloat(value.to_bits(), false),
+                })
+        };
+
         let int = choice((
             just("0x").ignore_then(digits(16).to_slice().map(|num| (16, num))),
             just("0b").ignore_then(digits(2).to_slice().map(|num| (2, num))),
             just("0").ignore_then(digits(8).to_slice().map(|num| (8, num))),
             text::int(10).map(|num| (10, num)),
         ))
-        .map(|(radix, src)| u64::from_str_radix(src, radix).expect("infallible"))
-        .map(Token::LitInteger);
+        .then(integer_suffix())
+        .validate(|((radix, src), (unsigned, longs)), e, emitter| {
+            let value = u64::from_str_radix(src, radix).unwrap_or_else(|_| {
+                emitter.emit(Rich::custom(e.span(), "integer constant is too large"));
+                0
+            });
+            Token::LitInteger(value, integer_type(value, radix == 10, unsigned, longs))
+        });
 
-        int
+        let char = none_of("\\'\n")
+            .validate(|c: char, e, emitter| {
+                if !c.is_ascii() {
+                    emitter.emit(Rich::custom(e.span(), "character does not fit in a char"));
+                }
+                c as u8
+            })
+            .or(escape())
+            .delimited_by(just('\''), just('\''))
+            .map(Token::LitChar);
+
+        // Escapes are decoded later by `unescape`, tokens only borrow from the source
+        let string = none_of("\\\"\n")
+            .ignored()
+            .or(escape().ignored())
+            .repeated()
+            .to_slice()
+            .delimited_by(just('"'), just('"'))
+            .map(Token::LitString);
+
+        choice((float, int, char, string))
     }
     .boxed();
 
@@ -23,9 +87,19 @@ pub fn lexer<'src>(
         just(")").to(Token::CloseParen),
         just("{").to(Token::OpenBrace),
         just("}").to(Token::CloseBrace),
+        just("[").to(Token::OpenBracket),
+        just("]").to(Token::CloseBracket),
         // Controls
         just(";").to(Token::Semicolon),
+        just(",").to(Token::Comma),
+        just("...").to(Token::Ellipsis),
         /* Operators */
+        // Member Access Operators
+        just(".").to(Token::Dot),
+        just("->").to(Token::Arrow),
+        // Increment and Decrement Operators
+        just("++").to(Token::PlusPlus),
+        just("--").to(Token::MinusMinus),
         // Compound Assignment Operators
         just("+=").to(Token::PlusEquals),
         just("-=").to(Token::MinusEquals),
@@ -73,9 +147,40 @@ pub fn lexer<'src>(
     let ident = text::ascii::ident()
         .map(|ident| match ident {
             "int" => Token::Int,
+            "char" => Token::Char,
+            "short" => Token::Short,
+            "long" => Token::Long,
+            "float" => Token::Float,
+            "double" => Token::Double,
+            "void" => Token::Void,
+            "signed" => Token::Signed,
+            "unsigned" => Token::Unsigned,
             "return" => Token::Return,
             "if" => Token::If,
             "else" => Token::Else,
+            "while" => Token::While,
+            "do" => Token::Do,
+            "for" => Token::For,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
+            "sizeof" => Token::Sizeof,
+            "_Alignof" => Token::Alignof,
+            "struct" => Token::Struct,
+            "union" => Token::Union,
+            "enum" => Token::Enum,
+            "typedef" => Token::Typedef,
+            "static" => Token::Static,
+            "extern" => Token::Extern,
+            "const" => Token::Const,
+            "volatile" => Token::Volatile,
+            "switch" => Token::Switch,
+            "case" => Token::Case,
+            "default" => Token::Default,
+            "goto" => Token::Goto,
+            "__builtin_va_list" => Token::VaList,
+            "__builtin_va_start" => Token::VaStart,
+            "__builtin_va_arg" => Token::VaArg,
+            "__builtin_va_end" => Token::VaEnd,
             s => Token::Identifier(s),
         })
         .boxed();
@@ -108,39 +213,339 @@ pub fn lexer<'src>(
         .collect()
 }
 
+/// The optional `u` and `l`/`ll` suffixes of an integer constant, in either order, as
+/// whether it is unsigned and its number of `l`s
+fn integer_suffix<'src>(
+) -> impl Parser<'src, &'src str, (bool, u8), extra::Err<Rich<'src, char, Span>>> + Clone {
+    let unsigned = one_of("uU");
+    let long = choice((just("ll").to(2), just("LL").to(2), one_of("lL").to(1)));
+
+    choice((
+        unsigned
+            .ignore_then(long.or_not())
+            .map(|longs| (true, longs)),
+        long.then(unsigned.or_not())
+            .map(|(longs, u)| (u.is_some(), Some(longs))),
+    ))
+    .or_not()
+    .map(|suffix| match suffix {
+        Some((unsigned, longs)) => (unsigned, longs.unwrap_or(0)),
+        None => (false, 0),
+    })
+}
+
+/// Picks the type of an integer constant following C11: the first of `int`, `long` and
+/// `long long`, starting from the length its suffix asks for, that can hold its value.
+/// Unsigned types are only considered for a `u` suffix, or alongside signed ones for
+/// octal, hex and binary constants. A decimal constant too large for any signed type is
+/// made `unsigned long long`, as gcc does.
+fn integer_type(value: u64, decimal: bool, unsigned: bool, longs: u8) -> IntegerType {
+    (longs..=2)
+        .flat_map(|longs| {
+            [
+                (!unsigned).then_some(IntegerType {
+                    signed: true,
+                    longs,
+                }),
+                (unsigned || !decimal).then_some(IntegerType {
+                    signed: false,
+                    longs,
+                }),
+            ]
+        })
+        .flatten()
+        .find(|ty| value <= ty.max())
+        .unwrap_or(IntegerType {
+            signed: false,
+            longs: 2,
+        })
+}
+
+/// Evaluates a hexadecimal floating constant from the hex digits of its mantissa, which may
+/// contain a point, and its binary exponent
+fn hex_float(mantissa: &str, exponent: &str) -> f64 {
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digit = |c: char| c.to_digit(16).expect("infallible") as u64;
+
+    // Digits beyond what a u64 holds only decide rounding, so they are folded into the
+    // lowest bit
+    let (mut value, mut scale, mut sticky) = (0u64, 0i64, false);
+    for c in int.chars() {
+        match value >> 60 {
+            0 => value = value << 4 | digit(c),
+            _ => (scale, sticky) = (scale + 4, sticky || digit(c) != 0),
+        }
+    }
+    for c in frac.chars() {
+        match value >> 60 {
+            0 => (value, scale) = (value << 4 | digit(c), scale - 4),
+            _ => sticky = sticky || digit(c) != 0,
+        }
+    }
+    value |= sticky as u64;
+
+    // Out of range exponents overflow to infinity or underflow to zero either way
+    let exponent = exponent
+        .parse::<i64>()
+        .unwrap_or(match exponent.starts_with('-') {
+            true => -100_000,
+            false => 100_000,
+        });
+    let exponent = (exponent + scale).clamp(-100_000, 100_000) as i32;
+
+    // Scaled in two steps, so that a subnormal result is not flushed to zero on the way
+    value as f64 * 2f64.powi(exponent / 2) * 2f64.powi(exponent - exponent / 2)
+}
+
+/// A backslash escape sequence in a character or string literal, malformed escapes are reported
+/// over the whole escape sequence
+fn escape<'src>() -> impl Parser<'src, &'src str, u8, extra::Err<Rich<'src, char, Span>>> + Clone {
+    just('\\')
+        .ignore_then(choice((
+            one_of("'\"?\\").map(|c: char| Ok(c as u8)),
+            just('a').to(Ok(0x07)),
+            just('b').to(Ok(0x08)),
+            just('f').to(Ok(0x0c)),
+            just('n').to(Ok(b'\n')),
+            just('r').to(Ok(b'\r')),
+            just('t').to(Ok(b'\t')),
+            just('v').to(Ok(0x0b)),
+            one_of("01234567")
+                .repeated()
+                .at_least(1)
+                .at_most(3)
+                .to_slice()
+                .map(
+                    |num| match u16::from_str_radix(num, 8).expect("infallible") {
+                        n if n > 0xff => Err("octal escape sequence out of range".to_owned()),
+                        n => Ok(n as u8),
+                    },
+                ),
+            just('x')
+                .ignore_then(digits(16).to_slice().or_not())
+                .map(|num| match num.map(|num| u8::from_str_radix(num, 16)) {
+                    Some(Ok(n)) => Ok(n),
+                    Some(Err(_)) => Err("hex escape sequence out of range".to_owned()),
+                    None => Err("\\x used with no following hex digits".to_owned()),
+                }),
+            any().map(|c| Err(format!("unknown escape sequence '\\{}'", c))),
+        )))
+        .validate(|escape: Result<u8, String>, e, emitter| {
+            escape.unwrap_or_else(|msg| {
+                emitter.emit(Rich::custom(e.span(), msg));
+                0
+            })
+        })
+}
+
+/// Decodes the contents of a string literal, as borrowed by `Token::LitString`
+pub fn unescape(raw: &str) -> Vec<u8> {
+    escape()
+        .map(|b| vec![b])
+        .or(any().map(|c: char| c.to_string().into_bytes()))
+        .repeated()
+        .collect::<Vec<_>>()
+        .parse(raw)
+        .into_output()
+        .unwrap_or_default()
+        .concat()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn keywords() {
-        let result = lexer().parse("int return if else").into_result();
+        let result = lexer()
+            .parse(
+                "int return if else while do for break continue sizeof struct union enum typedef char signed unsigned short long float double static extern switch case default goto _Alignof __builtin_va_list __builtin_va_start __builtin_va_arg __builtin_va_end const volatile void",
+            )
+            .into_result();
         assert_eq!(
             result,
             Ok(vec![
                 (Token::Int, Span::new(0, 3)),
                 (Token::Return, Span::new(4, 10)),
                 (Token::If, Span::new(11, 13)),
-                (Token::Else, Span::new(14, 18))
+                (Token::Else, Span::new(14, 18)),
+                (Token::While, Span::new(19, 24)),
+                (Token::Do, Span::new(25, 27)),
+                (Token::For, Span::new(28, 31)),
+                (Token::Break, Span::new(32, 37)),
+                (Token::Continue, Span::new(38, 46)),
+                (Token::Sizeof, Span::new(47, 53)),
+                (Token::Struct, Span::new(54, 60)),
+                (Token::Union, Span::new(61, 66)),
+                (Token::Enum, Span::new(67, 71)),
+                (Token::Typedef, Span::new(72, 79)),
+                (Token::Char, Span::new(80, 84)),
+                (Token::Signed, Span::new(85, 91)),
+                (Token::Unsigned, Span::new(92, 100)),
+                (Token::Short, Span::new(101, 106)),
+                (Token::Long, Span::new(107, 111)),
+                (Token::Float, Span::new(112, 117)),
+                (Token::Double, Span::new(118, 124)),
+                (Token::Static, Span::new(125, 131)),
+                (Token::Extern, Span::new(132, 138)),
+                (Token::Switch, Span::new(139, 145)),
+                (Token::Case, Span::new(146, 150)),
+                (Token::Default, Span::new(151, 158)),
+                (Token::Goto, Span::new(159, 163)),
+                (Token::Alignof, Span::new(164, 172)),
+                (Token::VaList, Span::new(173, 190)),
+                (Token::VaStart, Span::new(191, 209)),
+                (Token::VaArg, Span::new(210, 226)),
+                (Token::VaEnd, Span::new(227, 243)),
+                (Token::Const, Span::new(244, 249)),
+                (Token::Volatile, Span::new(250, 258)),
+                (Token::Void, Span::new(259, 263))
             ])
         );
     }
 
+    const INT: IntegerType = IntegerType {
+        signed: true,
+        longs: 0,
+    };
+
     #[test]
     fn lit_int() {
         let result = lexer().parse("0 123 0x123 0b101 0123").into_result();
         assert_eq!(
             result,
             Ok(vec![
-                (Token::LitInteger(0), Span::new(0, 1)),
-                (Token::LitInteger(123), Span::new(2, 5)),
-                (Token::LitInteger(0x123), Span::new(6, 11)),
-                (Token::LitInteger(0b101), Span::new(12, 17)),
-                (Token::LitInteger(0o123), Span::new(18, 22))
+                (Token::LitInteger(0, INT), Span::new(0, 1)),
+                (Token::LitInteger(123, INT), Span::new(2, 5)),
+                (Token::LitInteger(0x123, INT), Span::new(6, 11)),
+                (Token::LitInteger(0b101, INT), Span::new(12, 17)),
+                (Token::LitInteger(0o123, INT), Span::new(18, 22))
+            ])
+        );
+    }
+
+    #[test]
+    fn lit_int_types() {
+        let ty = |signed, longs| IntegerType { signed, longs };
+        for (src, value, expected) in [
+            ("2147483647", 0x7fffffff, ty(true, 0)),
+            ("2147483648", 0x80000000, ty(true, 1)),
+            ("0x80000000", 0x80000000, ty(false, 0)),
+            ("0xffffffffffffffff", u64::MAX, ty(false, 1)),
+            ("18446744073709551615", u64::MAX, ty(false, 2)),
+            ("1u", 1, ty(false, 0)),
+            ("1L", 1, ty(true, 1)),
+            ("1lu", 1, ty(false, 1)),
+            ("1ULL", 1, ty(false, 2)),
+            ("1ll", 1, ty(true, 2)),
+            ("4294967296u", 1 << 32, ty(false, 1)),
+        ] {
+            let result = lexer().parse(src).into_result();
+            assert_eq!(
+                result,
+                Ok(vec![(
+                    Token::LitInteger(value, expected),
+                    Span::new(0, src.len())
+                )]),
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn lit_float() {
+        let double = |value: f64| Token::LitFloat(value.to_bits(), false);
+        let float = |value: f32| Token::LitFloat((value as f64).to_bits(), true);
+        for (src, expected) in [
+            ("1.5", double(1.5)),
+            ("1.", double(1.0)),
+            (".25", double(0.25)),
+            ("1e3", double(1000.0)),
+            ("2.5E-1", double(0.25)),
+            ("0.1f", float(0.1)),
+            ("0x1p4", double(16.0)),
+            ("0x1.8p1", double(3.0)),
+            ("0x.8p0F", float(0.5)),
+            ("0x1p-1074", double(f64::from_bits(1))),
+            ("0x1.fffffffffffff8p0", double(2.0)),
+        ] {
+            let result = lexer().parse(src).into_result();
+            assert_eq!(
+                result,
+                Ok(vec![(expected, Span::new(0, src.len()))]),
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn lit_int_overflow() {
+        let errs = lexer().parse("1 99999999999999999999 2").into_errors();
+        assert_eq!(errs.len(), 1);
+        assert_eq!(*errs[0].span(), Span::new(2, 22));
+    }
+
+    #[test]
+    fn lit_char() {
+        let result = lexer()
+            .parse(r"'a' '\n' '\x41' '\101' '\0' '\'' '\\' '\xff'")
+            .into_result();
+        assert_eq!(
+            result,
+            Ok(vec![
+                (Token::LitChar(b'a'), Span::new(0, 3)),
+                (Token::LitChar(b'\n'), Span::new(4, 8)),
+                (Token::LitChar(0x41), Span::new(9, 15)),
+                (Token::LitChar(0o101), Span::new(16, 22)),
+                (Token::LitChar(0), Span::new(23, 27)),
+                (Token::LitChar(b'\''), Span::new(28, 32)),
+                (Token::LitChar(b'\\'), Span::new(33, 37)),
+                (Token::LitChar(0xff), Span::new(38, 44))
             ])
         );
     }
 
+    #[test]
+    fn lit_string() {
+        let result = lexer()
+            .parse(r#""" "hello\n" "a\"b" "\x41\101""#)
+            .into_result();
+        assert_eq!(
+            result,
+            Ok(vec![
+                (Token::LitString(""), Span::new(0, 2)),
+                (Token::LitString(r"hello\n"), Span::new(3, 12)),
+                (Token::LitString(r#"a\"b"#), Span::new(13, 19)),
+                (Token::LitString(r"\x41\101"), Span::new(20, 30))
+            ])
+        );
+    }
+
+    #[test]
+    fn unescape_string() {
+        assert_eq!(unescape(r"hello\n"), b"hello\n");
+        assert_eq!(unescape(r#"a\"b\\"#), b"a\"b\\");
+        assert_eq!(unescape(r"\x41\101\0"), b"AA\0");
+        assert_eq!(unescape("é"), "é".as_bytes());
+    }
+
+    #[test]
+    fn malformed_escapes() {
+        for (src, span) in [
+            (r"'\q'", Span::new(1, 3)),
+            (r"'\x'", Span::new(1, 3)),
+            (r"'\x100'", Span::new(1, 6)),
+            (r"'\777'", Span::new(1, 5)),
+            (r#""ab\qc""#, Span::new(3, 5)),
+        ] {
+            let errs = lexer().parse(src).into_errors();
+            assert_eq!(errs.len(), 1, "{}", src);
+            assert_eq!(*errs[0].span(), span, "{}", src);
+        }
+    }
+
     #[test]
     fn ident() {
         let result = lexer()
@@ -153,7 +558,7 @@ mod tests {
                 (Token::Identifier("hello_world"), Span::new(6, 17)),
                 (Token::Identifier("hello123"), Span::new(18, 26)),
                 (Token::Identifier("_123"), Span::new(27, 31)),
-                (Token::LitInteger(123), Span::new(32, 35)),
+                (Token::LitInteger(123, INT), Span::new(32, 35)),
                 (Token::Identifier("a"), Span::new(35, 36))
             ])
         );
@@ -161,14 +566,47 @@ mod tests {
 
     #[test]
     fn delimiters() {
-        let result = lexer().parse(r"(){}").into_result();
+        let result = lexer().parse(r"(){}[]").into_result();
         assert_eq!(
             result,
             Ok(vec![
                 (Token::OpenParen, Span::new(0, 1)),
                 (Token::CloseParen, Span::new(1, 2)),
                 (Token::OpenBrace, Span::new(2, 3)),
-                (Token::CloseBrace, Span::new(3, 4))
+                (Token::CloseBrace, Span::new(3, 4)),
+                (Token::OpenBracket, Span::new(4, 5)),
+                (Token::CloseBracket, Span::new(5, 6))
+            ])
+        );
+    }
+
+    #[test]
+    fn member_ops() {
+        let result = lexer().parse(". -> - > ...").into_result();
+        assert_eq!(
+            result,
+            Ok(vec![
+                (Token::Dot, Span::new(0, 1)),
+                (Token::Arrow, Span::new(2, 4)),
+                (Token::Minus, Span::new(5, 6)),
+                (Token::GreaterThan, Span::new(7, 8)),
+                (Token::Ellipsis, Span::new(9, 12))
+            ])
+        );
+    }
+
+    #[test]
+    fn increment_ops() {
+        let result = lexer().parse("++ -- +++ -->").into_result();
+        assert_eq!(
+            result,
+            Ok(vec![
+                (Token::PlusPlus, Span::new(0, 2)),
+                (Token::MinusMinus, Span::new(3, 5)),
+                (Token::PlusPlus, Span::new(6, 8)),
+                (Token::Plus, Span::new(8, 9)),
+                (Token::MinusMinus, Span::new(10, 12)),
+                (Token::GreaterThan, Span::new(12, 13))
             ])
         );
     }
@@ -270,7 +708,13 @@ mod tests {
 
     #[test]
     fn control_ops() {
-        let result = lexer().parse(";").into_result();
-        assert_eq!(result, Ok(vec![(Token::Semicolon, Span::new(0, 1))]));
+        let result = lexer().parse("; ,").into_result();
+        assert_eq!(
+            result,
+            Ok(vec![
+                (Token::Semicolon, Span::new(0, 1)),
+                (Token::Comma, Span::new(2, 3))
+            ])
+        );
     }
 }
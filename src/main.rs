@@ -8,7 +8,7 @@ use rcc::{
         Codegen,
     },
     lexer::lexer,
-    parser::parser,
+    parser::{parser, TypeNames},
 };
 use std::{fs, path::PathBuf};
 
@@ -41,7 +41,10 @@ fn main() -> eyre::Result<()> {
         Some(tokens) => {
             let (ast, parse_errs) = parser()
                 .map_with(|ast, e| (ast, e.span()))
-                .parse(tokens.as_slice().spanned((src.len()..src.len()).into()))
+                .parse_with_state(
+                    tokens.as_slice().spanned((src.len()..src.len()).into()),
+                    &mut TypeNames::new(),
+                )
                 .into_output_errors();
 
             if let Some((fns, _)) = ast {
@@ -50,8 +53,22 @@ fn main() -> eyre::Result<()> {
                 }
 
                 let mut em = Emitter::new();
+                let mut env = Environment::new();
 
-                match fns.code_gen(&mut LabelTracker::new(), &mut em, &mut Environment::new()) {
+                let result = fns.code_gen(&mut LabelTracker::new(), &mut em, &mut env);
+                for (warning, span) in env.warnings {
+                    Report::build(ReportKind::Warning, file_name.clone(), span.start)
+                        .with_message(warning.to_string())
+                        .with_labels(
+                            (warning, span)
+                                .into_label(file_name.clone())
+                                .into_iter()
+                                .map(|label| label.with_color(Color::Yellow)),
+                        )
+                        .finish()
+                        .eprint(sources([(file_name.clone(), src.clone())]))?
+                }
+                match result {
                     Ok(()) => fs::write(args.output, em.collect())?,
                     Err((err, span)) => {
                         Report::build(ReportKind::Error, file_name.clone(), span.start)
@@ -1,5 +1,14 @@
-use crate::common::{decl::FnDeclaration, Expr, Span, Spanned, Stmt, Token};
-use chumsky::prelude::*;
+use crate::{
+    common::{
+        decl::{Decl, Declarator, FnDeclaration, StorageClass},
+        init::{Designator, Initializer},
+        ty::{Qualifiers, TagKind},
+        Expr, Span, Spanned, Stmt, Token, Type, TypeSpec, UnaryOperator,
+    },
+    lexer::unescape,
+};
+use chumsky::{input::MapExtra, prelude::*};
+use std::collections::HashMap;
 
 /*
 C/C++ Operator Precedence
@@ -74,43 +83,703 @@ _Alignof    Align Of
 type ParserInput<'tokens, 'src> =
     chumsky::input::SpannedInput<Token<'src>, Span, &'tokens [(Token<'src>, Span)]>;
 
+type ParserExtra<'tokens, 'src> =
+    extra::Full<Rich<'tokens, Token<'src>, Span>, TypeNames<'src>, ()>;
+
+/// The identifiers declared so far, needed to tell `T * x;` declaring a pointer apart from
+/// a multiplication. Each scope maps a name to whether it names a type, so that ordinary
+/// declarations in inner scopes can hide a typedef.
+///
+/// Only parsers whose output is used record declarations, chumsky does not run `map_with`
+/// on parsers it merely checks.
+#[derive(Debug)]
+pub struct TypeNames<'src>(Vec<HashMap<&'src str, bool>>);
+
+impl Default for TypeNames<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'src> TypeNames<'src> {
+    /// Starts out in file scope, where `va_list` names a type as if `<stdarg.h>` were included
+    pub fn new() -> Self {
+        Self(vec![HashMap::from([("va_list", true)])])
+    }
+
+    pub fn is_type(&self, name: &str) -> bool {
+        self.0
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    fn declare(&mut self, name: &'src str, is_type: bool) {
+        if let Some(scope) = self.0.last_mut() {
+            scope.insert(name, is_type);
+        }
+    }
+
+    fn enter(&mut self) {
+        self.0.push(HashMap::new());
+    }
+
+    fn leave(&mut self) {
+        self.0.pop();
+    }
+}
+
 /// Parses a C Program
 pub fn parser<'tokens, 'src: 'tokens>() -> impl Parser<
     'tokens,
     ParserInput<'tokens, 'src>,
-    Vec<Spanned<FnDeclaration<'src>>>,
-    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+    Vec<Spanned<Decl<'src>>>,
+    ParserExtra<'tokens, 'src>,
 > + Clone {
     let ident = select! { Token::Identifier(s) => s }.labelled("identifier");
 
-    let fn_decl = just(Token::Int)
-        .then(ident)
-        .then_ignore(just(Token::OpenParen))
+    let param = param_with(ty(), assignment_expr())
+        .map_with(|(ty, ident), e| {
+            if let Some(ident) = ident {
+                e.state().declare(ident, false);
+            }
+            (ty, (ident, e.span()))
+        })
+        .labelled("parameter");
+
+    // Parameters share a scope with the outermost block of the body. A prototype has no
+    // body, its parameters going out of scope at the end of the declaration.
+    let fn_decl = storage_class()
+        .then(ty())
+        .then(ident.map_with(|ident, e| (ident, e.span())))
+        .then(enter_scope(Token::OpenParen))
+        .then(param_list(param))
         .then_ignore(just(Token::CloseParen))
-        .then(stmt().repeated().collect())
-        .map_with(|((_ty, name), body), e| (FnDeclaration(name, body), e.span()))
+        .then(choice((
+            stmt()
+                .repeated()
+                .collect()
+                .delimited_by(just(Token::OpenBrace), just(Token::CloseBrace))
+                .map(Some),
+            just(Token::Semicolon).map(|_| None),
+        )))
+        .map_with(
+            |(((((storage, ty), name), _), (params, variadic)), body), e| {
+                e.state().leave();
+                e.state().declare(name.0, false);
+                (
+                    Decl::Function(FnDeclaration(storage, ty, name, params, variadic, body)),
+                    e.span(),
+                )
+            },
+        )
         .labelled("function")
         .boxed();
 
-    fn_decl.repeated().collect().labelled("program")
+    let var_decl = storage_class()
+        .then(base_ty())
+        .then(declarators())
+        .then_ignore(just(Token::Semicolon))
+        .map_with(|((storage, ty), declarators), e| {
+            for declarator in &declarators {
+                e.state().declare(declarator.name.0, false);
+            }
+            (Decl::Variable(storage, ty, declarators), e.span())
+        })
+        .labelled("variable")
+        .boxed();
+
+    let type_decl = ty()
+        .then_ignore(just(Token::Semicolon))
+        .map_with(|ty, e| (Decl::Type(ty), e.span()))
+        .boxed();
+
+    let typedef_decl = typedef()
+        .map_with(|(ty, name), e| (Decl::Typedef(ty, name), e.span()))
+        .boxed();
+
+    choice((fn_decl, var_decl, type_decl, typedef_decl))
+        .repeated()
+        .collect()
+        .labelled("program")
 }
 
-/* Statements */
-fn stmt<'tokens, 'src: 'tokens>() -> impl Parser<
+/* Types */
+/// Parses a type along with its pointers, where only one name can be declared with it
+fn ty<'tokens, 'src: 'tokens>(
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, TypeSpec<'src>, ParserExtra<'tokens, 'src>> + Clone
+{
+    base_ty()
+        .foldl(pointer().repeated(), |ty, qualifiers| {
+            ty.pointer_to().qualified(qualifiers)
+        })
+        .labelled("type")
+}
+
+/// Parses any number of type qualifiers, which may be repeated
+fn qualifiers<'tokens, 'src: 'tokens>(
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, Qualifiers, ParserExtra<'tokens, 'src>> + Clone
+{
+    select! {
+        Token::Const => Qualifiers::CONST,
+        Token::Volatile => Qualifiers::VOLATILE,
+    }
+    .labelled("type qualifier")
+    .repeated()
+    .collect::<Vec<_>>()
+    .map(|qualifiers| {
+        qualifiers
+            .into_iter()
+            .fold(Qualifiers::default(), |a, b| a | b)
+    })
+}
+
+/// Parses the `*` of a pointer declarator and the qualifiers of the pointer that follow it,
+/// as in `* const`
+fn pointer<'tokens, 'src: 'tokens>(
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, Qualifiers, ParserExtra<'tokens, 'src>> + Clone
+{
+    just(Token::Star).ignore_then(qualifiers())
+}
+
+/// Parses the type a declaration starts with, which its declarators may each add pointers
+/// and array lengths to
+fn base_ty<'tokens, 'src: 'tokens>(
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, TypeSpec<'src>, ParserExtra<'tokens, 'src>> + Clone
+{
+    base_ty_with(assignment_expr())
+}
+
+/// Parses a base type whose array lengths and enumerator values are parsed by `expr`, so
+/// that types within expressions can use the expression parser being defined
+fn base_ty_with<'tokens, 'src: 'tokens>(
+    expr: impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+        + Clone
+        + 'tokens,
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, TypeSpec<'src>, ParserExtra<'tokens, 'src>> + Clone
+{
+    let ident = select! { Token::Identifier(s) => s }
+        .labelled("identifier")
+        .map_with(|ident, e| (ident, e.span()));
+
+    recursive(|base_ty| {
+        let ty = base_ty
+            .clone()
+            .foldl(pointer().repeated(), |ty: TypeSpec<'src>, qualifiers| {
+                ty.pointer_to().qualified(qualifiers)
+            })
+            .boxed();
+
+        // Like any declaration, a member declaration may declare several members
+        let member = base_ty
+            .then(
+                declarator_with(
+                    ident.map(|name| (name, false)),
+                    param_with(ty, expr.clone()).map(|(ty, _)| ty),
+                    expr.clone(),
+                )
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .collect::<Vec<_>>(),
+            )
+            .then_ignore(just(Token::Semicolon))
+            .map(|(ty, declarators)| {
+                declarators
+                    .into_iter()
+                    .map(|declarator| member_type(ty.clone(), declarator))
+                    .collect::<Vec<_>>()
+            })
+            .labelled("struct member");
+
+        let members = member
+            .repeated()
+            .collect::<Vec<_>>()
+            .map(|members| members.concat())
+            .delimited_by(just(Token::OpenBrace), just(Token::CloseBrace));
+
+        let r#struct = choice((
+            just(Token::Struct).to(TagKind::Struct),
+            just(Token::Union).to(TagKind::Union),
+        ))
+        .then(choice((
+            ident
+                .then(members.clone().or_not())
+                .map(|(tag, members)| (Some(tag), members)),
+            members.map(|members| (None, Some(members))),
+        )))
+        .map(|(kind, (tag, members))| TypeSpec::Struct(kind, tag, members))
+        .boxed();
+
+        let enumerators = ident
+            .then(just(Token::Equals).ignore_then(expr).or_not())
+            .map_with(|enumerator, e| {
+                e.state().declare(enumerator.0 .0, false);
+                enumerator
+            })
+            .labelled("enumerator")
+            .separated_by(just(Token::Comma))
+            .allow_trailing()
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .delimited_by(just(Token::OpenBrace), just(Token::CloseBrace));
+
+        let r#enum = just(Token::Enum)
+            .ignore_then(choice((
+                ident
+                    .then(enumerators.clone().or_not())
+                    .map(|(tag, enumerators)| TypeSpec::Enum(Some(tag), enumerators)),
+                enumerators.map(|enumerators| TypeSpec::Enum(None, Some(enumerators))),
+            )))
+            .boxed();
+
+        let typedef_name = ident
+            .try_map_with(|(name, span), e| match e.state().is_type(name) {
+                true => Ok(TypeSpec::Typedef((name, span))),
+                false => Err(Rich::custom(span, format!("'{}' is not a type", name))),
+            })
+            .boxed();
+
+        // Integer type specifiers may be written in any order, as in `long unsigned int`
+        let integer = choice((
+            just(Token::Signed),
+            just(Token::Unsigned),
+            just(Token::Char),
+            just(Token::Short),
+            just(Token::Int),
+            just(Token::Long),
+        ))
+        .repeated()
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .validate(|specifiers, e, emitter| {
+            integer_type(&specifiers).unwrap_or_else(|| {
+                emitter.emit(Rich::custom(
+                    e.span(),
+                    "invalid combination of type specifiers",
+                ));
+                TypeSpec::Int { signed: true }
+            })
+        });
+
+        let float = choice((
+            just(Token::Float).to(TypeSpec::Float),
+            just(Token::Double).to(TypeSpec::Double),
+        ));
+
+        let va_list = just(Token::VaList).to(TypeSpec::VaList);
+
+        // Qualifiers may come before or after the specifiers, as in `const int` and `int const`
+        qualifiers()
+            .then(choice((
+                just(Token::Void).to(TypeSpec::Void),
+                integer,
+                float,
+                r#struct,
+                r#enum,
+                typedef_name,
+                va_list,
+            )))
+            .then(qualifiers())
+            .map(|((before, ty), after)| ty.qualified(before | after))
+            .labelled("type")
+    })
+}
+
+/// Combines integer type specifiers into the type they name, if they name one. Plain `char`
+/// is signed.
+fn integer_type<'src>(specifiers: &[Token]) -> Option<TypeSpec<'src>> {
+    let count = |token| specifiers.iter().filter(|t| **t == token).count();
+    let (signed, unsigned) = (count(Token::Signed), count(Token::Unsigned));
+    let (char, short, int, long) = (
+        count(Token::Char),
+        count(Token::Short),
+        count(Token::Int),
+        count(Token::Long),
+    );
+
+    if signed + unsigned > 1
+        || char + short > 1
+        || int > 1
+        || long > 2
+        || (char > 0 && int + long > 0)
+        || (short > 0 && long > 0)
+    {
+        return None;
+    }
+
+    let signed = unsigned == 0;
+    Some(match (char, short, long) {
+        (1, _, _) => TypeSpec::Char { signed },
+        (_, 1, _) => TypeSpec::Short { signed },
+        (_, _, 1) => TypeSpec::Long { signed },
+        (_, _, 2) => TypeSpec::LongLong { signed },
+        _ => TypeSpec::Int { signed },
+    })
+}
+
+/// Parses the `[N]` suffixes of an array declarator, outermost first
+fn array_dims_with<'tokens, 'src: 'tokens>(
+    expr: impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+        + Clone,
+) -> impl Parser<
     'tokens,
     ParserInput<'tokens, 'src>,
-    Spanned<Stmt<'src>>,
-    extra::Err<Rich<'tokens, Token<'src>, Span>>,
+    Vec<Spanned<Expr<'src>>>,
+    ParserExtra<'tokens, 'src>,
+> + Clone {
+    expr.labelled("array length")
+        .delimited_by(just(Token::OpenBracket), just(Token::CloseBracket))
+        .repeated()
+        .collect()
+}
+
+/// Parses the comma separated declarators of a declaration, each with its own initializer
+fn declarators<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Vec<Declarator<'src>>,
+    ParserExtra<'tokens, 'src>,
+> + Clone {
+    let ident = select! { Token::Identifier(s) => s }
+        .labelled("identifier")
+        .map_with(|ident, e| (ident, e.span()));
+
+    let param = param_with(ty(), assignment_expr()).map(|(ty, _)| ty);
+
+    // The outermost array length may be left out for the initializer to decide
+    let name = ident.then(
+        just(Token::OpenBracket)
+            .then(just(Token::CloseBracket))
+            .or_not()
+            .map(|brackets| brackets.is_some()),
+    );
+
+    declarator_with(name, param, assignment_expr())
+        .then(just(Token::Equals).ignore_then(initializer()).or_not())
+        .map(|(declarator, init)| Declarator { init, ..declarator })
+        .separated_by(just(Token::Comma))
+        .at_least(1)
+        .collect()
+}
+
+/// Parses a declarator without its initializer. `name` parses the declared name, and
+/// whether its outermost array length is left out.
+fn declarator_with<'tokens, 'src: 'tokens>(
+    name: impl Parser<
+            'tokens,
+            ParserInput<'tokens, 'src>,
+            (Spanned<&'src str>, bool),
+            ParserExtra<'tokens, 'src>,
+        > + Clone,
+    param: impl Parser<'tokens, ParserInput<'tokens, 'src>, TypeSpec<'src>, ParserExtra<'tokens, 'src>>
+        + Clone,
+    expr: impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+        + Clone,
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, Declarator<'src>, ParserExtra<'tokens, 'src>> + Clone
+{
+    pointer()
+        .repeated()
+        .collect()
+        .then(direct_declarator(name, param, expr))
+        .map(
+            |(pointers, (function, (name, unknown_length), dims))| Declarator {
+                pointers,
+                function,
+                name,
+                dims,
+                unknown_length,
+                init: None,
+            },
+        )
+        .labelled("declarator")
+}
+
+/// Parses the initializer of a declarator: an expression, or a brace enclosed list of
+/// initializers, each of which may be designated as in `.x = 1` or `[3] = 7`
+fn initializer<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    Spanned<Initializer<'src>>,
+    ParserExtra<'tokens, 'src>,
+> + Clone {
+    let ident = select! { Token::Identifier(s) => s }
+        .labelled("identifier")
+        .map_with(|ident, e| (ident, e.span()));
+
+    recursive(|initializer| {
+        let designator = choice((
+            just(Token::Dot).ignore_then(ident).map(Designator::Member),
+            assignment_expr()
+                .delimited_by(just(Token::OpenBracket), just(Token::CloseBracket))
+                .map(Designator::Index),
+        ))
+        .labelled("designator");
+
+        let item = designator
+            .repeated()
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .then_ignore(just(Token::Equals))
+            .or_not()
+            .map(Option::unwrap_or_default)
+            .then(initializer);
+
+        choice((
+            item.separated_by(just(Token::Comma))
+                .allow_trailing()
+                .collect()
+                .delimited_by(just(Token::OpenBrace), just(Token::CloseBrace))
+                .map(Initializer::List),
+            assignment_expr().map(Initializer::Expr),
+        ))
+        .map_with(|init, e| (init, e.span()))
+        .boxed()
+    })
+    .labelled("initializer")
+}
+
+/// The parameters of a function type written in a declarator, and the qualifiers of each
+/// pointer to it
+type FnSuffix<'src> = Option<(Vec<Qualifiers>, Vec<TypeSpec<'src>>, bool)>;
+
+/// The parameter types of a function type, and whether they end with `...`
+type ParamTypes<'src> = (Vec<TypeSpec<'src>>, bool);
+
+/// Parses what follows the pointers of a declarator: a name given by `name` with its array
+/// lengths, or the parenthesised declarator of a pointer to a function such as
+/// `(*f[2])(int, char *)`, whose parameter types are given by `param`
+fn direct_declarator<'tokens, 'src: 'tokens, O>(
+    name: impl Parser<'tokens, ParserInput<'tokens, 'src>, O, ParserExtra<'tokens, 'src>> + Clone,
+    param: impl Parser<'tokens, ParserInput<'tokens, 'src>, TypeSpec<'src>, ParserExtra<'tokens, 'src>>
+        + Clone,
+    expr: impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+        + Clone,
+) -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    (FnSuffix<'src>, O, Vec<Spanned<Expr<'src>>>),
+    ParserExtra<'tokens, 'src>,
+> + Clone {
+    choice((
+        fn_declarator(name.clone(), param, expr.clone()).map(
+            |(pointers, name, dims, (params, variadic))| {
+                (Some((pointers, params, variadic)), name, dims)
+            },
+        ),
+        name.then(array_dims_with(expr))
+            .map(|(name, dims)| (None, name, dims)),
+    ))
+}
+
+/// Parses `(*name dims)(params)`, the declarator of a pointer to a function
+fn fn_declarator<'tokens, 'src: 'tokens, O>(
+    name: impl Parser<'tokens, ParserInput<'tokens, 'src>, O, ParserExtra<'tokens, 'src>> + Clone,
+    param: impl Parser<'tokens, ParserInput<'tokens, 'src>, TypeSpec<'src>, ParserExtra<'tokens, 'src>>
+        + Clone,
+    expr: impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+        + Clone,
+) -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    (
+        Vec<Qualifiers>,
+        O,
+        Vec<Spanned<Expr<'src>>>,
+        ParamTypes<'src>,
+    ),
+    ParserExtra<'tokens, 'src>,
+> + Clone {
+    pointer()
+        .repeated()
+        .at_least(1)
+        .collect()
+        .then(name)
+        .then(array_dims_with(expr))
+        .delimited_by(just(Token::OpenParen), just(Token::CloseParen))
+        .then(param_list(param).delimited_by(just(Token::OpenParen), just(Token::CloseParen)))
+        .map(|(((pointers, name), dims), params)| (pointers, name, dims, params))
+}
+
+/// Parses comma separated parameters, which a variadic function follows with `, ...`.
+/// `(void)` declares no parameters, while `()` leaves them unspecified, which is kept as
+/// a variadic function without parameters as that can't be declared otherwise.
+fn param_list<'tokens, 'src: 'tokens, O>(
+    param: impl Parser<'tokens, ParserInput<'tokens, 'src>, O, ParserExtra<'tokens, 'src>> + Clone,
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, (Vec<O>, bool), ParserExtra<'tokens, 'src>> + Clone
+{
+    let params = param
+        .separated_by(just(Token::Comma))
+        .at_least(1)
+        .collect()
+        .then(
+            just(Token::Comma)
+                .then(just(Token::Ellipsis))
+                .or_not()
+                .map(|ellipsis| ellipsis.is_some()),
+        );
+
+    // `void` may also start a parameter, as in `(void *p)`
+    choice((
+        just(Token::Void)
+            .then(just(Token::CloseParen).rewind())
+            .map(|_| (vec![], false)),
+        params,
+        empty().map(|_| (vec![], true)),
+    ))
+}
+
+/// Parses a parameter of type `ty`, with its name if it has one. Names may be left out, as
+/// they usually are in prototypes. Array parameters are adjusted to pointers, so the
+/// outermost length is optional.
+fn param_with<'tokens, 'src: 'tokens>(
+    ty: impl Parser<'tokens, ParserInput<'tokens, 'src>, TypeSpec<'src>, ParserExtra<'tokens, 'src>>
+        + Clone
+        + 'tokens,
+    expr: impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+        + Clone
+        + 'tokens,
+) -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    (TypeSpec<'src>, Option<&'src str>),
+    ParserExtra<'tokens, 'src>,
 > + Clone {
     let ident = select! { Token::Identifier(s) => s }.labelled("identifier");
 
-    recursive(|stmt| {
-        let stmt_block = stmt
+    recursive(|param| {
+        let function = ty
             .clone()
-            .repeated()
-            .collect()
-            .delimited_by(just(Token::OpenBrace), just(Token::CloseBrace))
-            .map_with(|stmts, e| (Stmt::Block(stmts), e.span()));
+            .then(fn_declarator(
+                ident.or_not(),
+                param.map(|(ty, _)| ty),
+                expr.clone(),
+            ))
+            .map(|(ty, (pointers, name, mut dims, (params, variadic)))| {
+                let adjust = !dims.is_empty();
+                if adjust {
+                    dims.remove(0);
+                }
+                let ty = declared_type(ty, Some((pointers, params, variadic)), dims);
+                match adjust {
+                    true => (ty.pointer_to(), name),
+                    false => (ty, name),
+                }
+            });
+
+        let other = ty
+            .then(ident.or_not())
+            .then(
+                expr.clone()
+                    .or_not()
+                    .delimited_by(just(Token::OpenBracket), just(Token::CloseBracket))
+                    .ignore_then(array_dims_with(expr))
+                    .or_not(),
+            )
+            .map(|((ty, name), dims)| match dims {
+                Some(dims) => (array_of(ty, dims).pointer_to(), name),
+                None => (ty, name),
+            });
+
+        choice((function, other)).boxed()
+    })
+}
+
+fn storage_class<'tokens, 'src: 'tokens>(
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, Option<StorageClass>, ParserExtra<'tokens, 'src>>
+       + Clone {
+    select! {
+        Token::Static => StorageClass::Static,
+        Token::Extern => StorageClass::Extern,
+    }
+    .or_not()
+}
+
+fn array_of<'src>(ty: TypeSpec<'src>, dims: Vec<Spanned<Expr<'src>>>) -> TypeSpec<'src> {
+    dims.into_iter().rev().fold(ty, TypeSpec::array_of)
+}
+
+/// The type declared by a declarator for a declaration starting with `ty`
+fn declared_type<'src>(
+    ty: TypeSpec<'src>,
+    function: FnSuffix<'src>,
+    dims: Vec<Spanned<Expr<'src>>>,
+) -> TypeSpec<'src> {
+    let ty = match function {
+        Some((pointers, params, variadic)) => pointers.into_iter().fold(
+            TypeSpec::Function(Box::new(ty), params, variadic),
+            |ty, qualifiers| ty.pointer_to().qualified(qualifiers),
+        ),
+        None => ty,
+    };
+    array_of(ty, dims)
+}
+
+/// The type and name of a struct member declared by `declarator`, for a declaration
+/// starting with `ty`
+fn member_type<'src>(
+    ty: TypeSpec<'src>,
+    declarator: Declarator<'src>,
+) -> (TypeSpec<'src>, Spanned<&'src str>) {
+    let ty = declarator
+        .pointers
+        .into_iter()
+        .fold(ty, |ty, qualifiers| ty.pointer_to().qualified(qualifiers));
+    (
+        declared_type(ty, declarator.function, declarator.dims),
+        declarator.name,
+    )
+}
+
+/// Matches `token`, opening a scope for the names declared after it. The parser that
+/// consumes the matching closing token must leave the scope again.
+fn enter_scope<'tokens, 'src: 'tokens>(
+    token: Token<'src>,
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, (), ParserExtra<'tokens, 'src>> + Clone {
+    just(token).map_with(
+        |_, e: &mut MapExtra<'tokens, '_, _, ParserExtra<'tokens, 'src>>| e.state().enter(),
+    )
+}
+
+/// Parses `typedef T name;`, declaring `name` as a type from then on
+fn typedef<'tokens, 'src: 'tokens>() -> impl Parser<
+    'tokens,
+    ParserInput<'tokens, 'src>,
+    (TypeSpec<'src>, Spanned<&'src str>),
+    ParserExtra<'tokens, 'src>,
+> + Clone {
+    let ident = select! { Token::Identifier(s) => s }
+        .labelled("identifier")
+        .map_with(|ident, e| (ident, e.span()));
+
+    let param = param_with(ty(), assignment_expr()).map(|(ty, _)| ty);
+
+    just(Token::Typedef)
+        .ignore_then(ty())
+        .then(direct_declarator(ident, param, assignment_expr()))
+        .then_ignore(just(Token::Semicolon))
+        .map_with(|(ty, (function, name, dims)), e| {
+            e.state().declare(name.0, true);
+            (declared_type(ty, function, dims), name)
+        })
+        .labelled("typedef")
+}
+
+/* Statements */
+fn stmt<'tokens, 'src: 'tokens>(
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Stmt<'src>>, ParserExtra<'tokens, 'src>>
+       + Clone {
+    let ident = select! { Token::Identifier(s) => s }.labelled("identifier");
+
+    recursive(|stmt| {
+        let stmt_block = enter_scope(Token::OpenBrace)
+            .then(stmt.clone().repeated().collect())
+            .then(just(Token::CloseBrace))
+            .map_with(|((_, stmts), _), e| {
+                e.state().leave();
+                (Stmt::Block(stmts), e.span())
+            });
 
         let stmt_expr = expr()
             .then_ignore(just(Token::Semicolon))
@@ -118,16 +787,21 @@ fn stmt<'tokens, 'src: 'tokens>() -> impl Parser<
             .boxed();
 
         let stmt_return = just(Token::Return)
-            .ignore_then(expr())
+            .ignore_then(expr().or_not())
             .then_ignore(just(Token::Semicolon))
             .map_with(|expr, e| (Stmt::Return(expr), e.span()))
             .boxed();
 
-        let stmt_declare = just(Token::Int)
-            .then(ident.map_with(|ident, e| (ident, e.span())))
-            .then(just(Token::Equals).ignore_then(expr()).or_not())
+        let stmt_declare = storage_class()
+            .then(base_ty())
+            .then(declarators())
             .then_ignore(just(Token::Semicolon))
-            .map_with(|((_ty, ident), expr), e| (Stmt::Declare(ident, expr), e.span()))
+            .map_with(|((storage, ty), declarators), e| {
+                for declarator in &declarators {
+                    e.state().declare(declarator.name.0, false);
+                }
+                (Stmt::Declare(storage, ty, declarators), e.span())
+            })
             .boxed();
 
         let stmt_if = just(Token::If)
@@ -138,19 +812,115 @@ fn stmt<'tokens, 'src: 'tokens>() -> impl Parser<
             .then(just(Token::Else).ignore_then(stmt.clone()).or_not())
             .map_with(|((cond, then), r#else), e| {
                 (
-                    Stmt::If(cond, Box::new(then), r#else.map(|stmt| Box::new(stmt))),
+                    Stmt::If(cond, Box::new(then), r#else.map(Box::new)),
                     e.span(),
                 )
             });
 
+        let stmt_declare_type = ty()
+            .then_ignore(just(Token::Semicolon))
+            .map_with(|ty, e| (Stmt::DeclareType(ty), e.span()))
+            .boxed();
+
         let stmt_empty = just(Token::Semicolon).map_with(|_, e| (Stmt::Empty, e.span()));
 
+        let stmt_while = just(Token::While)
+            .ignore_then(expr().delimited_by(just(Token::OpenParen), just(Token::CloseParen)))
+            .then(stmt.clone())
+            .map_with(|(cond, body), e| (Stmt::While(cond, Box::new(body)), e.span()))
+            .boxed();
+
+        let stmt_do_while = just(Token::Do)
+            .ignore_then(stmt.clone())
+            .then_ignore(just(Token::While))
+            .then(expr().delimited_by(just(Token::OpenParen), just(Token::CloseParen)))
+            .then_ignore(just(Token::Semicolon))
+            .map_with(|(body, cond), e| (Stmt::DoWhile(Box::new(body), cond), e.span()))
+            .boxed();
+
+        // The init clause may declare names that are visible only inside the loop
+        let stmt_for = just(Token::For)
+            .ignore_then(enter_scope(Token::OpenParen))
+            .then(choice((
+                stmt_declare.clone(),
+                stmt_expr.clone(),
+                stmt_empty,
+            )))
+            .then(expr().or_not())
+            .then_ignore(just(Token::Semicolon))
+            .then(expr().or_not())
+            .then_ignore(just(Token::CloseParen))
+            .then(stmt.clone())
+            .map_with(|((((_, init), cond), step), body), e| {
+                e.state().leave();
+                (
+                    Stmt::For(Box::new(init), cond, step, Box::new(body)),
+                    e.span(),
+                )
+            })
+            .boxed();
+
+        let stmt_switch = just(Token::Switch)
+            .ignore_then(expr().delimited_by(just(Token::OpenParen), just(Token::CloseParen)))
+            .then(stmt.clone())
+            .map_with(|(cond, body), e| (Stmt::Switch(cond, Box::new(body)), e.span()))
+            .boxed();
+
+        let stmt_case = just(Token::Case)
+            .ignore_then(assignment_expr())
+            .then_ignore(just(Token::Colon))
+            .then(stmt.clone())
+            .map_with(|(value, body), e| (Stmt::Case(value, Box::new(body)), e.span()))
+            .boxed();
+
+        let stmt_default = just(Token::Default)
+            .ignore_then(just(Token::Colon))
+            .ignore_then(stmt.clone())
+            .map_with(|body, e| (Stmt::Default(Box::new(body)), e.span()))
+            .boxed();
+
+        let stmt_label = ident
+            .map_with(|ident, e| (ident, e.span()))
+            .then_ignore(just(Token::Colon))
+            .then(stmt.clone())
+            .map_with(|(label, body), e| (Stmt::Label(label, Box::new(body)), e.span()))
+            .boxed();
+
+        let stmt_goto = just(Token::Goto)
+            .ignore_then(ident.map_with(|ident, e| (ident, e.span())))
+            .then_ignore(just(Token::Semicolon))
+            .map_with(|label, e| (Stmt::Goto(label), e.span()));
+
+        let stmt_break = just(Token::Break)
+            .then_ignore(just(Token::Semicolon))
+            .map_with(|_, e| (Stmt::Break, e.span()));
+
+        let stmt_typedef = typedef()
+            .map_with(|(ty, name), e| (Stmt::Typedef(ty, name), e.span()))
+            .boxed();
+
+        let stmt_continue = just(Token::Continue)
+            .then_ignore(just(Token::Semicolon))
+            .map_with(|_, e| (Stmt::Continue, e.span()));
+
         choice((
             stmt_if,
+            stmt_while,
+            stmt_switch,
+            stmt_case,
+            stmt_default,
+            stmt_label,
+            stmt_goto,
+            stmt_do_while,
+            stmt_for,
+            stmt_break,
+            stmt_continue,
             stmt_block,
+            stmt_typedef,
             stmt_expr,
             stmt_return,
             stmt_declare,
+            stmt_declare_type,
             stmt_empty,
         ))
         .labelled("statement")
@@ -158,43 +928,217 @@ fn stmt<'tokens, 'src: 'tokens>() -> impl Parser<
 }
 
 /* Expressions */
-fn expr<'tokens, 'src: 'tokens>() -> impl Parser<
-    'tokens,
-    ParserInput<'tokens, 'src>,
-    Spanned<Expr<'src>>,
-    extra::Err<Rich<'tokens, Token<'src>, Span>>,
-> + Clone {
+enum Postfix<'src> {
+    Call(Vec<Spanned<Expr<'src>>>),
+    Index(Spanned<Expr<'src>>),
+    Member(Spanned<&'src str>),
+    Arrow(Spanned<&'src str>),
+    Increment,
+    Decrement,
+}
+
+/// Parses an expression, which may be a sequence of expressions joined by the comma operator
+fn expr<'tokens, 'src: 'tokens>(
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+       + Clone {
+    comma(assignment_expr()).labelled("expression")
+}
+
+/// Joins `operand`s with the comma operator
+fn comma<'tokens, 'src: 'tokens>(
+    operand: impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+        + Clone,
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+       + Clone {
+    operand.clone().foldl_with(
+        just(Token::Comma).then(operand).repeated(),
+        |lhs, (op, rhs), e| {
+            Expr::new_binary(lhs, op.try_into().expect("infallible"), rhs, e.span())
+        },
+    )
+}
+
+/// Parses an expression without a top level comma operator, for where commas separate
+/// things instead, as between arguments
+fn assignment_expr<'tokens, 'src: 'tokens>(
+) -> impl Parser<'tokens, ParserInput<'tokens, 'src>, Spanned<Expr<'src>>, ParserExtra<'tokens, 'src>>
+       + Clone {
     let literal = select! {
-        Token::LitInteger(i) => Expr::LiteralInteger(i),
+        Token::LitInteger(i, ty) => Expr::LiteralInteger(i, ty.into()),
+        // Character constants have type int, with the value of the char
+        Token::LitChar(c) => Expr::LiteralInteger(c as i8 as u64, Type::Int { signed: true }),
+        Token::LitFloat(bits, false) => Expr::LiteralFloat(f64::from_bits(bits), Type::Double),
+        Token::LitFloat(bits, true) => Expr::LiteralFloat(f64::from_bits(bits), Type::Float),
     }
+    // Adjacent string literals are concatenated, after their escapes are decoded
+    .or(select! { Token::LitString(s) => s }
+        .repeated()
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .map(|parts| Expr::LiteralString(parts.into_iter().flat_map(unescape).collect())))
     .map_with(|expr, e| (expr, e.span()))
     .boxed()
     .labelled("value");
 
-    let variable = select! {
-        Token::Identifier(v) => Expr::Variable(v),
-    }
-    .map_with(|expr, e| (expr, e.span()))
-    .boxed()
-    .labelled("variable");
+    // Typedef names are not expressions, which is what makes `T * x;` a declaration
+    let variable = select! { Token::Identifier(v) => v }
+        .try_map_with(
+            |v, e: &mut MapExtra<'tokens, '_, _, ParserExtra<'tokens, 'src>>| match e
+                .state()
+                .is_type(v)
+            {
+                true => Err(Rich::custom(
+                    e.span(),
+                    format!("unexpected type name '{}'", v),
+                )),
+                false => Ok((Expr::Variable(v), e.span())),
+            },
+        )
+        .boxed()
+        .labelled("variable");
+
+    let member = select! { Token::Identifier(s) => s }
+        .map_with(|name, e| (name, e.span()))
+        .labelled("member");
+
+    recursive(|assignment| {
+        let expr = comma(assignment.clone());
+
+        // Told apart from an expression by the typedef names in scope. Array types have no
+        // declarator to hang their lengths on.
+        let ty = base_ty_with(assignment.clone())
+            .foldl(pointer().repeated(), |ty: TypeSpec<'src>, qualifiers| {
+                ty.pointer_to().qualified(qualifiers)
+            })
+            .boxed();
+        let type_name = ty
+            .clone()
+            .then(direct_declarator(
+                empty(),
+                param_with(ty, assignment.clone()).map(|(ty, _)| ty),
+                assignment.clone(),
+            ))
+            .map(|(ty, (function, _, dims))| declared_type(ty, function, dims))
+            .labelled("type name")
+            .boxed();
+
+        // The `<stdarg.h>` macros are spelt as their builtins or as the plain names, which stay
+        // ordinary identifiers wherever they are not called
+        let builtin = |token, name: &'static str| {
+            just(token)
+                .ignored()
+                .or(select! { Token::Identifier(s) if s == name => () })
+        };
+
+        let va_start = builtin(Token::VaStart, "va_start")
+            .ignore_then(
+                assignment
+                    .clone()
+                    .then_ignore(just(Token::Comma))
+                    .then(assignment.clone())
+                    .delimited_by(just(Token::OpenParen), just(Token::CloseParen)),
+            )
+            .map_with(|(ap, param), e| (Expr::VaStart(Box::new(ap), Box::new(param)), e.span()));
+
+        let va_arg = builtin(Token::VaArg, "va_arg")
+            .ignore_then(
+                assignment
+                    .clone()
+                    .then_ignore(just(Token::Comma))
+                    .then(type_name.clone())
+                    .delimited_by(just(Token::OpenParen), just(Token::CloseParen)),
+            )
+            .map_with(|(ap, ty), e| (Expr::VaArg(Box::new(ap), ty), e.span()));
+
+        let va_end = builtin(Token::VaEnd, "va_end")
+            .ignore_then(
+                assignment
+                    .clone()
+                    .delimited_by(just(Token::OpenParen), just(Token::CloseParen)),
+            )
+            .map_with(|ap, e| (Expr::VaEnd(Box::new(ap)), e.span()));
 
-    recursive(|expr| {
         let atom = literal
             .or(expr
                 .clone()
                 .delimited_by(just(Token::OpenParen), just(Token::CloseParen)))
+            .or(choice((va_start, va_arg, va_end)))
             .or(variable)
             .boxed();
 
-        let unary = choice((
-            just(Token::Plus),
-            just(Token::Minus),
-            just(Token::Exclamation),
-            just(Token::Tilde),
-        ))
-        .repeated()
-        .foldr_with(atom, |op, rhs, e| {
-            Expr::new_unary(op.try_into().expect("infallible"), rhs, e.span())
+        let postfix = atom
+            .foldl_with(
+                choice((
+                    assignment
+                        .clone()
+                        .separated_by(just(Token::Comma))
+                        .collect()
+                        .delimited_by(just(Token::OpenParen), just(Token::CloseParen))
+                        .map(Postfix::Call),
+                    expr.clone()
+                        .delimited_by(just(Token::OpenBracket), just(Token::CloseBracket))
+                        .map(Postfix::Index),
+                    just(Token::Dot).ignore_then(member).map(Postfix::Member),
+                    just(Token::Arrow).ignore_then(member).map(Postfix::Arrow),
+                    just(Token::PlusPlus).map(|_| Postfix::Increment),
+                    just(Token::MinusMinus).map(|_| Postfix::Decrement),
+                ))
+                .repeated(),
+                |lhs, op, e| match op {
+                    Postfix::Call(args) => Expr::new_call(lhs, args, e.span()),
+                    Postfix::Index(index) => Expr::new_index(lhs, index, e.span()),
+                    Postfix::Member(name) => Expr::new_member(lhs, name, e.span()),
+                    // p->m is (*p).m
+                    Postfix::Arrow(name) => Expr::new_member(
+                        Expr::new_unary(UnaryOperator::Deref, lhs, e.span()),
+                        name,
+                        e.span(),
+                    ),
+                    Postfix::Increment => {
+                        Expr::new_unary(UnaryOperator::PostIncrement, lhs, e.span())
+                    }
+                    Postfix::Decrement => {
+                        Expr::new_unary(UnaryOperator::PostDecrement, lhs, e.span())
+                    }
+                },
+            )
+            .boxed();
+
+        let type_name = type_name
+            .delimited_by(just(Token::OpenParen), just(Token::CloseParen))
+            .boxed();
+
+        let unary = recursive(|unary| {
+            let sizeof_type = just(Token::Sizeof)
+                .ignore_then(type_name.clone())
+                .map_with(|ty, e| (Expr::SizeOfType(ty), e.span()));
+
+            let alignof = just(Token::Alignof)
+                .ignore_then(type_name.clone())
+                .map_with(|ty, e| (Expr::AlignOf(ty), e.span()));
+
+            let cast = type_name
+                .then(unary.clone())
+                .map_with(|(ty, rhs), e| Expr::new_cast(ty, rhs, e.span()));
+
+            let prefix = choice((
+                just(Token::Plus),
+                just(Token::Minus),
+                just(Token::Exclamation),
+                just(Token::Tilde),
+                just(Token::Star),
+                just(Token::And),
+                just(Token::PlusPlus),
+                just(Token::MinusMinus),
+                just(Token::Sizeof),
+            ))
+            .then(unary)
+            .map_with(|(op, rhs), e| match op {
+                Token::Sizeof => Expr::new_sizeof(rhs, e.span()),
+                op => Expr::new_unary(op.try_into().expect("infallible"), rhs, e.span()),
+            });
+
+            choice((sizeof_type, alignof, cast, prefix, postfix))
         })
         .boxed();
 
@@ -352,3 +1296,602 @@ fn expr<'tokens, 'src: 'tokens>() -> impl Parser<
         assignment.labelled("expression")
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::BinaryOperator, lexer};
+
+    fn parse_decls(src: &str) -> Vec<Spanned<Decl<'_>>> {
+        let tokens = lexer().parse(src).into_result().expect("lexes");
+        let decls = parser()
+            .parse_with_state(
+                tokens.as_slice().spanned((src.len()..src.len()).into()),
+                &mut TypeNames::new(),
+            )
+            .into_result()
+            .expect("parses");
+        decls
+    }
+
+    fn parse_body(src: &str) -> Vec<Spanned<Stmt<'_>>> {
+        match parse_decls(src).into_iter().last() {
+            Some((Decl::Function(FnDeclaration(_, _, _, _, _, Some(body))), _)) => body,
+            _ => panic!("expected a function"),
+        }
+    }
+
+    /// The name of each declarator, with its number of pointers and whether it is initialized
+    fn names<'a>(declarators: &'a [Declarator]) -> Vec<(&'a str, usize, bool)> {
+        declarators
+            .iter()
+            .map(|d| (d.name.0, d.pointers.len(), d.init.is_some()))
+            .collect()
+    }
+
+    #[test]
+    fn typedef_names() {
+        let body = parse_body("typedef int T; int f() { T * x; int y; y * x; }");
+        match &body[0].0 {
+            Stmt::Declare(None, TypeSpec::Typedef(("T", _)), declarators) => {
+                assert_eq!(names(declarators), [("x", 1, false)])
+            }
+            _ => panic!("expected a declaration"),
+        }
+        assert!(matches!(body[2].0, Stmt::Expression(_)));
+    }
+
+    #[test]
+    fn typedef_shadowing() {
+        let body = parse_body("typedef int T; int f() { { int T; T * 2; } T * x; }");
+        match &body[0].0 {
+            Stmt::Block(stmts) => assert!(matches!(stmts[1].0, Stmt::Expression(_))),
+            _ => panic!("expected a block"),
+        }
+        match &body[1].0 {
+            Stmt::Declare(None, TypeSpec::Typedef(("T", _)), declarators) => {
+                assert_eq!(names(declarators), [("x", 1, false)])
+            }
+            _ => panic!("expected a declaration"),
+        }
+
+        // Parameters hide typedefs throughout the body
+        let body = parse_body("typedef int T; int f(int T) { T * 2; }");
+        assert!(matches!(body[0].0, Stmt::Expression(_)));
+    }
+
+    #[test]
+    fn parameters() {
+        let src = "int f(int a, int b) { g(1, 2, 3, 4, 5, 6, 7, 8); }";
+        match &parse_decls(src)[0].0 {
+            Decl::Function(FnDeclaration(_, _, ("f", _), params, false, Some(_))) => {
+                assert!(matches!(
+                    params[..],
+                    [
+                        (TypeSpec::Int { signed: true }, (Some("a"), _)),
+                        (TypeSpec::Int { signed: true }, (Some("b"), _))
+                    ]
+                ))
+            }
+            _ => panic!("expected a definition"),
+        }
+
+        // Arguments past the sixth are parsed like the rest, in order
+        match &parse_body(src)[0].0 {
+            Stmt::Expression((Expr::Call(callee, args), _)) => {
+                assert!(matches!(callee.0, Expr::Variable("g")));
+                let values = args
+                    .iter()
+                    .map(|arg| match arg.0 {
+                        Expr::LiteralInteger(i, _) => i,
+                        _ => panic!("expected a literal"),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(values, [1, 2, 3, 4, 5, 6, 7, 8]);
+            }
+            _ => panic!("expected a call"),
+        }
+    }
+
+    #[test]
+    fn string_concatenation() {
+        let body = parse_body(r#"int f() { "ab" "c\n" ""; }"#);
+        match &body[0].0 {
+            Stmt::Expression((Expr::LiteralString(bytes), _)) => assert_eq!(bytes, b"abc\n"),
+            _ => panic!("expected a string literal"),
+        }
+    }
+
+    #[test]
+    fn integer_specifiers() {
+        let body = parse_body("int f() { unsigned long int a; long long b; short unsigned c; }");
+        assert!(matches!(
+            body[0].0,
+            Stmt::Declare(None, TypeSpec::Long { signed: false }, _)
+        ));
+        assert!(matches!(
+            body[1].0,
+            Stmt::Declare(None, TypeSpec::LongLong { signed: true }, _)
+        ));
+        assert!(matches!(
+            body[2].0,
+            Stmt::Declare(None, TypeSpec::Short { signed: false }, _)
+        ));
+
+        for specifiers in [
+            "long char",
+            "signed unsigned",
+            "long long long",
+            "short long",
+        ] {
+            let src = format!("int f() {{ {} x; }}", specifiers);
+            let tokens = lexer().parse(&src).into_result().expect("lexes");
+            let result = parser()
+                .parse_with_state(
+                    tokens.as_slice().spanned((src.len()..src.len()).into()),
+                    &mut TypeNames::new(),
+                )
+                .into_result();
+            assert!(result.is_err(), "{}", specifiers);
+        }
+    }
+
+    #[test]
+    fn global_variables() {
+        let decls = parse_decls("int x = 1; char *s[2]; int f() { return x; }");
+        match &decls[0].0 {
+            Decl::Variable(None, TypeSpec::Int { signed: true }, declarators) => {
+                assert_eq!(names(declarators), [("x", 0, true)])
+            }
+            _ => panic!("expected a variable"),
+        }
+        match &decls[1].0 {
+            Decl::Variable(None, TypeSpec::Char { signed: true }, declarators) => {
+                assert_eq!(names(declarators), [("s", 1, false)]);
+                assert_eq!(declarators[0].dims.len(), 1);
+            }
+            _ => panic!("expected a variable"),
+        }
+        assert!(matches!(decls[2].0, Decl::Function(_)));
+    }
+
+    #[test]
+    fn storage_classes() {
+        let decls =
+            parse_decls("static int x; extern char c; static int f() { static long n = 1; }");
+        assert!(matches!(
+            decls[0].0,
+            Decl::Variable(Some(StorageClass::Static), _, _)
+        ));
+        assert!(matches!(
+            decls[1].0,
+            Decl::Variable(Some(StorageClass::Extern), _, _)
+        ));
+        match &decls[2].0 {
+            Decl::Function(FnDeclaration(
+                Some(StorageClass::Static),
+                _,
+                ("f", _),
+                _,
+                _,
+                Some(body),
+            )) => {
+                assert!(matches!(
+                    body[0].0,
+                    Stmt::Declare(Some(StorageClass::Static), _, _)
+                ))
+            }
+            _ => panic!("expected a function"),
+        }
+    }
+
+    #[test]
+    fn prototypes() {
+        let decls = parse_decls("typedef int T; int f(T, char *); int f(T a, char *b) { a; }");
+        match &decls[1].0 {
+            Decl::Function(FnDeclaration(_, _, ("f", _), params, _, None)) => {
+                assert!(matches!(
+                    params[..],
+                    [(_, (None, _)), (TypeSpec::Pointer(_), (None, _))]
+                ))
+            }
+            _ => panic!("expected a prototype"),
+        }
+        match &decls[2].0 {
+            Decl::Function(FnDeclaration(_, _, ("f", _), params, _, Some(_))) => {
+                assert!(matches!(
+                    params[..],
+                    [(_, (Some("a"), _)), (_, (Some("b"), _))]
+                ))
+            }
+            _ => panic!("expected a definition"),
+        }
+    }
+
+    #[test]
+    fn void_types() {
+        let decls = parse_decls(
+            "void *malloc(unsigned long); int cmp(const void *, const void *); void (*p)(void);",
+        );
+        assert!(matches!(
+            &decls[0].0,
+            Decl::Function(FnDeclaration(_, TypeSpec::Pointer(ret), ("malloc", _), _, false, None))
+                if **ret == TypeSpec::Void
+        ));
+        assert!(matches!(
+            &decls[1].0,
+            Decl::Function(FnDeclaration(_, _, ("cmp", _), params, false, None))
+                if params.len() == 2 && matches!(&params[0].0, TypeSpec::Pointer(ty)
+                    if **ty == TypeSpec::Void.qualified(Qualifiers::CONST))
+        ));
+        match &decls[2].0 {
+            Decl::Variable(_, TypeSpec::Void, declarators) => {
+                assert!(
+                    matches!(&declarators[0].function, Some((pointers, params, false)) if pointers.len() == 1 && params.is_empty())
+                )
+            }
+            _ => panic!("expected a declaration"),
+        }
+    }
+
+    #[test]
+    fn void_functions() {
+        let src = "void free(void *); void srand(unsigned); void exit(int); \
+                   void f(int *p) { if (!p) return; *p = 1; }";
+        let decls = parse_decls(src);
+        for (decl, name) in decls.iter().zip(["free", "srand", "exit"]) {
+            assert!(matches!(
+                &decl.0,
+                Decl::Function(FnDeclaration(_, TypeSpec::Void, (n, _), params, false, None))
+                    if *n == name && params.len() == 1
+            ));
+        }
+
+        let body = parse_body(src);
+        assert!(matches!(
+            &body[0].0,
+            Stmt::If(_, then, None) if matches!(then.0, Stmt::Return(None))
+        ));
+    }
+
+    #[test]
+    fn void_params() {
+        let decls = parse_decls("int f(void); int f(void) { return 0; }");
+        assert!(matches!(
+            &decls[0].0,
+            Decl::Function(FnDeclaration(_, _, ("f", _), params, false, None)) if params.is_empty()
+        ));
+        assert!(matches!(
+            &decls[1].0,
+            Decl::Function(FnDeclaration(_, _, ("f", _), params, false, Some(_))) if params.is_empty()
+        ));
+    }
+
+    #[test]
+    fn switch_labels() {
+        let body = parse_body("int f(int x) { switch (x) { case 1: case 2: x; default: break; } }");
+        let stmts = match &body[0].0 {
+            Stmt::Switch(_, body) => match &body.0 {
+                Stmt::Block(stmts) => stmts,
+                _ => panic!("expected a block"),
+            },
+            _ => panic!("expected a switch"),
+        };
+        match &stmts[0].0 {
+            Stmt::Case(_, body) => assert!(matches!(body.0, Stmt::Case(_, _))),
+            _ => panic!("expected a case"),
+        }
+        match &stmts[1].0 {
+            Stmt::Default(body) => assert!(matches!(body.0, Stmt::Break)),
+            _ => panic!("expected a default"),
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let body = parse_body("typedef int T; int f() { T: goto T; x: x; T y; }");
+        match &body[0].0 {
+            Stmt::Label(("T", _), body) => assert!(matches!(body.0, Stmt::Goto(("T", _)))),
+            _ => panic!("expected a label"),
+        }
+        match &body[1].0 {
+            Stmt::Label(("x", _), body) => assert!(matches!(body.0, Stmt::Expression(_))),
+            _ => panic!("expected a label"),
+        }
+        assert!(matches!(body[2].0, Stmt::Declare(None, _, _)));
+    }
+
+    #[test]
+    fn increments() {
+        let body = parse_body("int f(int *p, int x) { *p++; - --x; }");
+        match &body[0].0 {
+            Stmt::Expression((Expr::Unary(UnaryOperator::Deref, rhs), _)) => assert!(matches!(
+                rhs.0,
+                Expr::Unary(UnaryOperator::PostIncrement, _)
+            )),
+            _ => panic!("expected a dereference"),
+        }
+        match &body[1].0 {
+            Stmt::Expression((Expr::Unary(UnaryOperator::Minus, rhs), _)) => {
+                assert!(matches!(rhs.0, Expr::Unary(UnaryOperator::PreDecrement, _)))
+            }
+            _ => panic!("expected a negation"),
+        }
+    }
+
+    #[test]
+    fn declarators() {
+        let body = parse_body("int f() { int a = 1, *b, c[2], d = a + 1; }");
+        match &body[0].0 {
+            Stmt::Declare(None, TypeSpec::Int { signed: true }, declarators) => {
+                assert_eq!(
+                    names(declarators),
+                    [
+                        ("a", 0, true),
+                        ("b", 1, false),
+                        ("c", 0, false),
+                        ("d", 0, true)
+                    ]
+                );
+                assert_eq!(declarators[2].dims.len(), 1);
+            }
+            _ => panic!("expected a declaration"),
+        }
+
+        let decls = parse_decls("struct S { int x; } *p, s;");
+        match &decls[0].0 {
+            Decl::Variable(None, TypeSpec::Struct(_, _, Some(_)), declarators) => {
+                assert_eq!(names(declarators), [("p", 1, false), ("s", 0, false)])
+            }
+            _ => panic!("expected a variable"),
+        }
+    }
+
+    #[test]
+    fn member_declarators() {
+        let decls = parse_decls("struct P { int x, *y, z[2]; float a, b; };");
+        match &decls[0].0 {
+            Decl::Type(TypeSpec::Struct(_, _, Some(members))) => assert!(matches!(
+                &members[..],
+                [
+                    (TypeSpec::Int { signed: true }, ("x", _)),
+                    (TypeSpec::Pointer(_), ("y", _)),
+                    (TypeSpec::Array(_, _), ("z", _)),
+                    (TypeSpec::Float, ("a", _)),
+                    (TypeSpec::Float, ("b", _))
+                ]
+            )),
+            _ => panic!("expected a struct"),
+        }
+    }
+
+    #[test]
+    fn comma_operator() {
+        let body = parse_body("int f(int a, int b) { a = 1, b = 2; f(a, (a, b)); }");
+        match &body[0].0 {
+            Stmt::Expression((Expr::Binary(lhs, BinaryOperator::Comma, _), _)) => {
+                assert!(matches!(lhs.0, Expr::Binary(_, BinaryOperator::Eq, _)))
+            }
+            _ => panic!("expected a comma expression"),
+        }
+        match &body[1].0 {
+            Stmt::Expression((Expr::Call(_, args), _)) => {
+                assert_eq!(args.len(), 2);
+                assert!(matches!(
+                    args[1].0,
+                    Expr::Binary(_, BinaryOperator::Comma, _)
+                ));
+            }
+            _ => panic!("expected a call"),
+        }
+    }
+
+    #[test]
+    fn function_pointers() {
+        let src = "typedef int (*F)(int); struct S { int (*f)(char *, int); }; \
+                   int g(int (*cmp)(long *, long *), int n); \
+                   int h() { int *(*fs[2])(int); (F)(*fs[0])(1); (int (*)(int))0; }";
+        let decls = parse_decls(src);
+        assert!(matches!(
+            &decls[0].0,
+            Decl::Typedef(TypeSpec::Pointer(f), ("F", _)) if matches!(**f, TypeSpec::Function(_, _, _))
+        ));
+        match &decls[1].0 {
+            Decl::Type(TypeSpec::Struct(_, _, Some(members))) => {
+                assert!(matches!(&members[0], (TypeSpec::Pointer(_), ("f", _))))
+            }
+            _ => panic!("expected a struct"),
+        }
+        match &decls[2].0 {
+            Decl::Function(FnDeclaration(_, _, ("g", _), params, false, None)) => {
+                assert!(matches!(
+                    params[..],
+                    [
+                        (TypeSpec::Pointer(_), (Some("cmp"), _)),
+                        (_, (Some("n"), _))
+                    ]
+                ))
+            }
+            _ => panic!("expected a prototype"),
+        }
+
+        let body = parse_body(src);
+        match &body[0].0 {
+            Stmt::Declare(None, TypeSpec::Int { .. }, declarators) => {
+                assert_eq!(names(declarators), [("fs", 1, false)]);
+                assert_eq!(declarators[0].dims.len(), 1);
+                assert!(
+                    matches!(&declarators[0].function, Some((pointers, params, false)) if pointers.len() == 1 && params.len() == 1)
+                );
+            }
+            _ => panic!("expected a declaration"),
+        }
+        match &body[1].0 {
+            Stmt::Expression((Expr::Cast(TypeSpec::Typedef(("F", _)), rhs), _)) => {
+                assert!(matches!(rhs.0, Expr::Call(_, _)))
+            }
+            _ => panic!("expected a cast"),
+        }
+        assert!(matches!(
+            &body[2].0,
+            Stmt::Expression((Expr::Cast(TypeSpec::Pointer(f), _), _))
+                if matches!(**f, TypeSpec::Function(_, _, _))
+        ));
+    }
+
+    #[test]
+    fn variadics() {
+        let src = "int printf(char *fmt, ...); \
+                   int f(int n, ...) { va_list ap; int (*p)(char *, ...); \
+                   va_start(ap, n); va_arg(ap, int *); va_end(ap); }";
+        let decls = parse_decls(src);
+        assert!(matches!(
+            &decls[0].0,
+            Decl::Function(FnDeclaration(_, _, ("printf", _), params, true, None)) if params.len() == 1
+        ));
+        assert!(matches!(
+            &decls[1].0,
+            Decl::Function(FnDeclaration(_, _, ("f", _), _, true, Some(_)))
+        ));
+
+        let body = parse_body(src);
+        assert!(matches!(
+            body[0].0,
+            Stmt::Declare(None, TypeSpec::Typedef(("va_list", _)), _)
+        ));
+        match &body[1].0 {
+            Stmt::Declare(None, TypeSpec::Int { .. }, declarators) => {
+                assert!(
+                    matches!(&declarators[0].function, Some((pointers, params, true)) if pointers.len() == 1 && params.len() == 1)
+                )
+            }
+            _ => panic!("expected a declaration"),
+        }
+        assert!(matches!(
+            &body[2].0,
+            Stmt::Expression((Expr::VaStart(ap, param), _))
+                if matches!(ap.0, Expr::Variable("ap")) && matches!(param.0, Expr::Variable("n"))
+        ));
+        assert!(matches!(
+            body[3].0,
+            Stmt::Expression((Expr::VaArg(_, TypeSpec::Pointer(_)), _))
+        ));
+        assert!(matches!(body[4].0, Stmt::Expression((Expr::VaEnd(_), _))));
+    }
+
+    #[test]
+    fn va_builtins() {
+        let body = parse_body(
+            "int f(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); \
+             __builtin_va_arg(ap, int); __builtin_va_end(ap); }",
+        );
+        assert!(matches!(
+            body[0].0,
+            Stmt::Declare(None, TypeSpec::VaList, _)
+        ));
+        assert!(matches!(
+            body[1].0,
+            Stmt::Expression((Expr::VaStart(_, _), _))
+        ));
+        assert!(matches!(
+            body[2].0,
+            Stmt::Expression((Expr::VaArg(_, _), _))
+        ));
+        assert!(matches!(body[3].0, Stmt::Expression((Expr::VaEnd(_), _))));
+
+        // The plain names are identifiers, which declarations may take
+        let body = parse_body("int f() { int va_list = 1; int va_end = va_list; return va_end; }");
+        assert!(matches!(
+            &body[1].0,
+            Stmt::Declare(None, TypeSpec::Int { .. }, declarators)
+                if matches!(declarators[0].init, Some((Initializer::Expr((Expr::Variable("va_list"), _)), _)))
+        ));
+        assert!(matches!(
+            body[2].0,
+            Stmt::Return(Some((Expr::Variable("va_end"), _)))
+        ));
+    }
+
+    #[test]
+    fn type_names() {
+        let body = parse_body(
+            "typedef int T; int f(int x) { (char)-x; (T *)(x); sizeof(T[2]); sizeof(x); _Alignof(T); }",
+        );
+        match &body[0].0 {
+            Stmt::Expression((Expr::Cast(TypeSpec::Char { signed: true }, rhs), _)) => {
+                assert!(matches!(rhs.0, Expr::Unary(UnaryOperator::Minus, _)))
+            }
+            _ => panic!("expected a cast"),
+        }
+        match &body[1].0 {
+            Stmt::Expression((Expr::Cast(TypeSpec::Pointer(_), rhs), _)) => {
+                assert!(matches!(rhs.0, Expr::Variable("x")))
+            }
+            _ => panic!("expected a cast"),
+        }
+        assert!(matches!(
+            body[2].0,
+            Stmt::Expression((Expr::SizeOfType(TypeSpec::Array(_, _)), _))
+        ));
+        assert!(matches!(body[3].0, Stmt::Expression((Expr::SizeOf(_), _))));
+        assert!(matches!(
+            body[4].0,
+            Stmt::Expression((Expr::AlignOf(TypeSpec::Typedef(("T", _))), _))
+        ));
+    }
+
+    #[test]
+    fn unspecified_params() {
+        let decls = parse_decls("int f(); int g(void); int (*p)();");
+        assert!(matches!(
+            &decls[0].0,
+            Decl::Function(FnDeclaration(_, _, ("f", _), params, true, None)) if params.is_empty()
+        ));
+        assert!(matches!(
+            &decls[1].0,
+            Decl::Function(FnDeclaration(_, _, ("g", _), params, false, None)) if params.is_empty()
+        ));
+        match &decls[2].0 {
+            Decl::Variable(_, _, declarators) => assert!(
+                matches!(&declarators[0].function, Some((_, params, true)) if params.is_empty())
+            ),
+            _ => panic!("expected a declaration"),
+        }
+    }
+
+    #[test]
+    fn qualifiers() {
+        let int = Box::new(TypeSpec::Int { signed: true });
+        let body = parse_body(
+            "int f() { const int a; int volatile const b; int *const *volatile p; (const char *)0; }",
+        );
+        match &body[0].0 {
+            Stmt::Declare(None, TypeSpec::Qualified(ty, Qualifiers::CONST), _) => {
+                assert_eq!(*ty, int)
+            }
+            _ => panic!("expected a const declaration"),
+        }
+        match &body[1].0 {
+            Stmt::Declare(None, TypeSpec::Qualified(ty, qualifiers), _) => {
+                assert_eq!(*ty, int);
+                assert_eq!(*qualifiers, Qualifiers::CONST | Qualifiers::VOLATILE);
+            }
+            _ => panic!("expected a const volatile declaration"),
+        }
+        match &body[2].0 {
+            Stmt::Declare(None, TypeSpec::Int { .. }, declarators) => {
+                assert_eq!(
+                    declarators[0].pointers,
+                    [Qualifiers::CONST, Qualifiers::VOLATILE]
+                )
+            }
+            _ => panic!("expected a declaration"),
+        }
+        match &body[3].0 {
+            Stmt::Expression((Expr::Cast(TypeSpec::Pointer(ty), _), _)) => {
+                assert!(matches!(**ty, TypeSpec::Qualified(_, Qualifiers::CONST)))
+            }
+            _ => panic!("expected a cast"),
+        }
+    }
+}